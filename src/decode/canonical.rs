@@ -0,0 +1,259 @@
+use crate::marker::Marker;
+
+use byteorder::{BigEndian, ByteOrder};
+#[cfg(any(feature = "alloc", feature = "std"))]
+extern crate alloc;
+#[cfg(any(feature = "alloc", feature = "std"))]
+use alloc::vec::Vec;
+
+/// Bits of the only NaN allowed in canonical form, the same as [`f32::NAN`]
+const CANONICAL_NAN_F32: u32 = 0x7fc0_0000;
+/// Bits of the only NaN allowed in canonical form, the same as [`f64::NAN`]
+const CANONICAL_NAN_F64: u64 = 0x7ff8_0000_0000_0000;
+
+/// Checks if `buf` holds exactly one value in canonical form
+///
+/// A value is canonical if
+///  - every integer, string, binary, array, map and ext uses the smallest marker that can hold it
+///  - every NaN is the quiet NaN without payload
+///  - the keys of every map are sorted by their encoded bytes, without duplicates
+///
+/// The smallest marker is judged by the markers the enabled features allow, which is what the canonical mode of the
+/// serializer writes. For example a map of 20 entries is only canonical as `map 16` if feature `map16` is active,
+/// and as `map 32` otherwise.
+///
+/// The input is checked in a single pass without recursion. Without feature `alloc`, values with arrays and maps
+/// nested deeper than [`MAX_CANONICAL_DEPTH`] aren't considered canonical.
+pub fn is_canonical(buf: &[u8]) -> bool {
+    let mut pos = 0;
+    let mut levels = Levels::new();
+    loop {
+        if let Some(level) = levels.last_mut() {
+            if level.map {
+                if level.remaining % 2 == 0 {
+                    level.key_start = pos;
+                } else {
+                    // the key ends where its value starts
+                    let key = &buf[level.key_start..pos];
+                    if level.prev_key_end > 0 && &buf[level.prev_key_start..level.prev_key_end] >= key {
+                        return false;
+                    }
+                    level.prev_key_start = level.key_start;
+                    level.prev_key_end = pos;
+                }
+            }
+            level.remaining -= 1;
+        }
+        let Some((len, nested)) = check_item(&buf[pos..]) else {
+            return false;
+        };
+        let map = matches!(Marker::from_u8(buf[pos]), Marker::FixMap(_) | Marker::Map16 | Marker::Map32);
+        pos += len;
+        if nested > 0 && !levels.push(Level::new(nested, map)) {
+            return false;
+        }
+        while levels.last_mut().is_some_and(|level| level.remaining == 0) {
+            levels.pop();
+        }
+        if levels.is_empty() {
+            return pos == buf.len();
+        }
+    }
+}
+
+/// An array or map whose content is being checked
+#[derive(Clone, Copy)]
+struct Level {
+    /// Values still to be checked, keys and values count separately for maps
+    remaining: usize,
+    map: bool,
+    /// Start of the current key of a map
+    key_start: usize,
+    /// Span of the previous key of a map, empty before the first one
+    prev_key_start: usize,
+    prev_key_end: usize,
+}
+
+impl Level {
+    const fn new(remaining: usize, map: bool) -> Self {
+        Level {
+            remaining,
+            map,
+            key_start: 0,
+            prev_key_start: 0,
+            prev_key_end: 0,
+        }
+    }
+}
+
+/// The open arrays and maps, innermost last
+#[cfg(any(feature = "alloc", feature = "std"))]
+struct Levels(Vec<Level>);
+
+#[cfg(any(feature = "alloc", feature = "std"))]
+impl Levels {
+    const fn new() -> Self {
+        Levels(Vec::new())
+    }
+    fn push(&mut self, level: Level) -> bool {
+        self.0.push(level);
+        true
+    }
+    fn pop(&mut self) {
+        self.0.pop();
+    }
+    fn last_mut(&mut self) -> Option<&mut Level> {
+        self.0.last_mut()
+    }
+    const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Number of arrays and maps [`is_canonical`] accepts nested in each other without feature `alloc`
+pub const MAX_CANONICAL_DEPTH: usize = 64;
+
+/// The open arrays and maps, innermost last
+#[cfg(not(any(feature = "alloc", feature = "std")))]
+struct Levels {
+    levels: [Level; MAX_CANONICAL_DEPTH],
+    len: usize,
+}
+
+#[cfg(not(any(feature = "alloc", feature = "std")))]
+impl Levels {
+    const fn new() -> Self {
+        Levels {
+            levels: [Level::new(0, false); MAX_CANONICAL_DEPTH],
+            len: 0,
+        }
+    }
+    const fn push(&mut self, level: Level) -> bool {
+        if self.len == MAX_CANONICAL_DEPTH {
+            return false;
+        }
+        self.levels[self.len] = level;
+        self.len += 1;
+        true
+    }
+    const fn pop(&mut self) {
+        self.len -= 1;
+    }
+    fn last_mut(&mut self) -> Option<&mut Level> {
+        self.levels[..self.len].last_mut()
+    }
+    const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Checks the item at the start of `buf`, without the content of arrays and maps
+///
+/// Returns the length of the item and the number of nested values following it, or `None` if it isn't canonical.
+fn check_item(buf: &[u8]) -> Option<(usize, usize)> {
+    let marker = Marker::from_u8(*buf.first()?);
+    let (minimal, len, nested) = match marker {
+        Marker::FixPos(_) | Marker::FixNeg(_) | Marker::Null | Marker::True | Marker::False => (true, 1, 0),
+        Marker::U8 => (uint(buf, 1)? > 0x7f, 2, 0),
+        Marker::U16 => (uint(buf, 2)? > 0xff, 3, 0),
+        Marker::U32 => (uint(buf, 4)? > 0xffff, 5, 0),
+        Marker::U64 => (uint(buf, 8)? > 0xffff_ffff, 9, 0),
+        // non negative values have to use the unsigned markers
+        Marker::I8 => (int(buf, 1)? < -0x20, 2, 0),
+        Marker::I16 => (int(buf, 2)? < -0x80, 3, 0),
+        Marker::I32 => (int(buf, 4)? < -0x8000, 5, 0),
+        Marker::I64 => {
+            let v = int(buf, 8)?;
+            (v < -0x8000_0000 || (v > 0xffff_ffff && !cfg!(feature = "u64")), 9, 0)
+        }
+        Marker::F32 => {
+            let bits = uint(buf, 4)? as u32;
+            (!f32::from_bits(bits).is_nan() || bits == CANONICAL_NAN_F32, 5, 0)
+        }
+        Marker::F64 => {
+            let bits = uint(buf, 8)?;
+            (!f64::from_bits(bits).is_nan() || bits == CANONICAL_NAN_F64, 9, 0)
+        }
+        Marker::FixStr(n) => (true, 1 + n as usize, 0),
+        Marker::Str8 => {
+            let n = uint(buf, 1)? as usize;
+            (n > 0x1f, 2 + n, 0)
+        }
+        Marker::Bin8 => (true, 2 + uint(buf, 1)? as usize, 0),
+        Marker::Str16 | Marker::Bin16 => {
+            let n = uint(buf, 2)? as usize;
+            (n > 0xff, 3 + n, 0)
+        }
+        Marker::Str32 => {
+            let n = uint(buf, 4)? as usize;
+            (n > 0xffff || !cfg!(feature = "str16"), 5 + n, 0)
+        }
+        Marker::Bin32 => {
+            let n = uint(buf, 4)? as usize;
+            (n > 0xffff || !cfg!(feature = "bin16"), 5 + n, 0)
+        }
+        Marker::FixArray(n) => (true, 1, n as usize),
+        Marker::Array16 => {
+            let n = uint(buf, 2)? as usize;
+            (n > 0xf, 3, n)
+        }
+        Marker::Array32 => {
+            let n = uint(buf, 4)? as usize;
+            (n > 0xffff || (n > 0xf && !cfg!(feature = "array16")), 5, n)
+        }
+        Marker::FixMap(n) => (true, 1, 2 * n as usize),
+        Marker::Map16 => {
+            let n = uint(buf, 2)? as usize;
+            (n > 0xf, 3, n.checked_mul(2)?)
+        }
+        Marker::Map32 => {
+            let n = uint(buf, 4)? as usize;
+            (n > 0xffff || (n > 0xf && !cfg!(feature = "map16")), 5, n.checked_mul(2)?)
+        }
+        Marker::FixExt1 => (true, 3, 0),
+        Marker::FixExt2 => (true, 4, 0),
+        Marker::FixExt4 => (true, 6, 0),
+        Marker::FixExt8 => (true, 10, 0),
+        Marker::FixExt16 => (true, 18, 0),
+        Marker::Ext8 => {
+            let n = uint(buf, 1)? as usize;
+            (!fits_fixext(n), 3 + n, 0)
+        }
+        Marker::Ext16 => {
+            let n = uint(buf, 2)? as usize;
+            (!fits_fixext(n) && !fits_ext8(n), 4 + n, 0)
+        }
+        Marker::Ext32 => {
+            let n = uint(buf, 4)? as usize;
+            (!fits_fixext(n) && !fits_ext8(n) && !fits_ext16(n), 6 + n, 0)
+        }
+        Marker::Reserved => return None,
+    };
+    // every nested value takes at least one byte
+    if !minimal || len > buf.len() || nested > buf.len() - len {
+        return None;
+    }
+    Some((len, nested))
+}
+
+const fn fits_fixext(n: usize) -> bool {
+    matches!(n, 1 | 2 | 4 | 8 | 16) && cfg!(feature = "fixext")
+}
+
+const fn fits_ext8(n: usize) -> bool {
+    n <= 0xff && cfg!(feature = "ext8")
+}
+
+const fn fits_ext16(n: usize) -> bool {
+    n <= 0xffff && cfg!(feature = "ext16")
+}
+
+/// Reads the `n` byte unsigned integer following the marker
+fn uint(buf: &[u8], n: usize) -> Option<u64> {
+    buf.get(1..=n).map(|b| BigEndian::read_uint(b, n))
+}
+
+/// Reads the `n` byte signed integer following the marker
+fn int(buf: &[u8], n: usize) -> Option<i64> {
+    buf.get(1..=n).map(|b| BigEndian::read_int(b, n))
+}
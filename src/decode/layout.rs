@@ -0,0 +1,92 @@
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::marker::Marker;
+
+/// The buffer ends inside a value
+pub(crate) struct Truncated {
+    /// Marker of the item that is cut off, [`Marker::Reserved`] if the buffer ends before it
+    pub marker: Marker,
+    /// Least number of bytes missing
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    pub missing: usize,
+}
+
+/// Length of the value at the start of `buf`
+///
+/// Only the markers and lengths are looked at, nested arrays and maps are walked without recursion.
+pub(crate) fn value_len(buf: &[u8]) -> Result<usize, Truncated> {
+    let mut pos = 0;
+    // values still to be checked, the content of arrays and maps follows their header. Each takes at least one byte.
+    let mut pending = 1_usize;
+    while pending > 0 {
+        pending -= 1;
+        let Some(&byte) = buf.get(pos) else {
+            return Err(Truncated {
+                marker: Marker::Reserved,
+                missing: pending + 1,
+            });
+        };
+        let marker = Marker::from_u8(byte);
+        let header_end = pos + 1 + len_bytes(marker);
+        if header_end > buf.len() {
+            return Err(Truncated {
+                marker,
+                missing: header_end - buf.len() + pending,
+            });
+        }
+        let (data_len, nested) = body(marker, read_len(marker, &buf[pos..]));
+        let end = header_end.saturating_add(data_len);
+        if end > buf.len() {
+            return Err(Truncated {
+                marker,
+                missing: (end - buf.len()).saturating_add(pending),
+            });
+        }
+        pos = end;
+        pending = pending.saturating_add(nested);
+    }
+    Ok(pos)
+}
+
+/// Number of bytes holding the length after `marker`, `0` for items of fixed size
+pub(crate) const fn len_bytes(marker: Marker) -> usize {
+    match marker {
+        Marker::Str8 | Marker::Bin8 | Marker::Ext8 => 1,
+        Marker::Str16 | Marker::Bin16 | Marker::Ext16 | Marker::Array16 | Marker::Map16 => 2,
+        Marker::Str32 | Marker::Bin32 | Marker::Ext32 | Marker::Array32 | Marker::Map32 => 4,
+        _ => 0,
+    }
+}
+
+/// Reads the length following the marker at the start of `header`, which has to hold [`len_bytes`] bytes after it
+pub(crate) fn read_len(marker: Marker, header: &[u8]) -> usize {
+    match len_bytes(marker) {
+        0 => 0,
+        n => BigEndian::read_uint(&header[1..=n], n) as usize,
+    }
+}
+
+/// Number of data bytes after the header of an item with the given `marker` and length, and the number of values
+/// nested in it
+pub(crate) const fn body(marker: Marker, len: usize) -> (usize, usize) {
+    match marker {
+        Marker::FixPos(_) | Marker::FixNeg(_) | Marker::Null | Marker::True | Marker::False | Marker::Reserved => (0, 0),
+        Marker::U8 | Marker::I8 => (1, 0),
+        Marker::U16 | Marker::I16 => (2, 0),
+        Marker::U32 | Marker::I32 | Marker::F32 => (4, 0),
+        Marker::U64 | Marker::I64 | Marker::F64 => (8, 0),
+        Marker::FixStr(n) => (n as usize, 0),
+        Marker::Str8 | Marker::Str16 | Marker::Str32 | Marker::Bin8 | Marker::Bin16 | Marker::Bin32 => (len, 0),
+        // the type byte comes first
+        Marker::FixExt1 => (2, 0),
+        Marker::FixExt2 => (3, 0),
+        Marker::FixExt4 => (5, 0),
+        Marker::FixExt8 => (9, 0),
+        Marker::FixExt16 => (17, 0),
+        Marker::Ext8 | Marker::Ext16 | Marker::Ext32 => (len.saturating_add(1), 0),
+        Marker::FixArray(n) => (0, n as usize),
+        Marker::Array16 | Marker::Array32 => (0, len),
+        Marker::FixMap(n) => (0, 2 * n as usize),
+        Marker::Map16 | Marker::Map32 => (0, len.saturating_mul(2)),
+    }
+}
@@ -0,0 +1,163 @@
+use zerocopy::ByteSlice;
+
+use super::Error;
+#[cfg(all(feature = "serde", feature = "std"))]
+use crate::marker::Marker;
+
+/// Resource limit a value exceeded, see [`Limits`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    /// [`Limits::max_elements`]
+    Elements,
+    /// [`Limits::max_str_len`]
+    StrLen,
+    /// [`Limits::max_bin_len`]
+    BinLen,
+    /// [`Limits::max_map_entries`]
+    MapEntries,
+    /// [`Limits::max_alloc_bytes`]
+    AllocBytes,
+}
+
+/// Bounds on the resources a decoded value may claim, for input that isn't trusted
+///
+/// Every limit is checked against the headers of the input before the content is visited, so a short message can't
+/// make the decoder reserve more memory than allowed. Exceeding a limit fails with
+/// [`Error::LimitExceeded`]. All limits are unbounded by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    max_elements: usize,
+    max_str_len: usize,
+    max_bin_len: usize,
+    max_map_entries: usize,
+    max_alloc_bytes: usize,
+}
+
+impl Limits {
+    /// Limits that allow everything
+    pub const fn new() -> Self {
+        Limits {
+            max_elements: usize::MAX,
+            max_str_len: usize::MAX,
+            max_bin_len: usize::MAX,
+            max_map_entries: usize::MAX,
+            max_alloc_bytes: usize::MAX,
+        }
+    }
+    /// Largest number of array elements and map entries in the whole value
+    pub const fn max_elements(mut self, max: usize) -> Self {
+        self.max_elements = max;
+        self
+    }
+    /// Largest length of a single string in bytes
+    pub const fn max_str_len(mut self, max: usize) -> Self {
+        self.max_str_len = max;
+        self
+    }
+    /// Largest length of a single binary in bytes
+    pub const fn max_bin_len(mut self, max: usize) -> Self {
+        self.max_bin_len = max;
+        self
+    }
+    /// Largest number of entries of a single map
+    pub const fn max_map_entries(mut self, max: usize) -> Self {
+        self.max_map_entries = max;
+        self
+    }
+    /// Largest sum of the lengths of the strings and binaries in the whole value that are copied into owned types like
+    /// `String` or `serde_bytes::ByteBuf`, or by self-describing types like `serde_json::Value` and untagged enums.
+    /// Borrowed `&str` and `&[u8]` and skipped values don't count
+    pub const fn max_alloc_bytes(mut self, max: usize) -> Self {
+        self.max_alloc_bytes = max;
+        self
+    }
+
+    /// Like [`read_str`](super::read_str), failing if the string is longer than [`Limits::max_str_len`]
+    pub fn read_str<'a>(&self, buf: &'a [u8]) -> Result<(&'a str, usize), Error> {
+        let (s, len) = super::read_str(buf)?;
+        check(s.len(), self.max_str_len, Limit::StrLen)?;
+        Ok((s, len))
+    }
+    /// Like [`read_bin`](super::read_bin), failing if the binary is longer than [`Limits::max_bin_len`]
+    pub fn read_bin<B: ByteSlice>(&self, buf: B) -> Result<(B, usize), Error> {
+        let (bin, len) = super::read_bin(buf)?;
+        check(bin.len(), self.max_bin_len, Limit::BinLen)?;
+        Ok((bin, len))
+    }
+    /// Like [`read_array_len`](super::read_array_len), failing if the array has more than [`Limits::max_elements`]
+    /// elements
+    pub fn read_array_len<B: ByteSlice>(&self, buf: B) -> Result<(usize, usize), Error> {
+        let (len, header_len) = super::read_array_len(buf)?;
+        check(len, self.max_elements, Limit::Elements)?;
+        Ok((len, header_len))
+    }
+    /// Like [`read_map_len`](super::read_map_len), failing if the map has more than [`Limits::max_map_entries`] or
+    /// [`Limits::max_elements`] entries
+    pub fn read_map_len<B: ByteSlice>(&self, buf: B) -> Result<(usize, usize), Error> {
+        let (len, header_len) = super::read_map_len(buf)?;
+        check(len, self.max_map_entries, Limit::MapEntries)?;
+        check(len, self.max_elements, Limit::Elements)?;
+        Ok((len, header_len))
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Tracks the resources used so far by the value being decoded
+#[cfg(feature = "serde")]
+pub(crate) struct Budget {
+    elements: usize,
+    alloc_bytes: usize,
+}
+
+#[cfg(feature = "serde")]
+impl Budget {
+    pub(crate) const fn new() -> Self {
+        Budget { elements: 0, alloc_bytes: 0 }
+    }
+    /// Accounts for an array or map with `len` elements or entries
+    pub(crate) const fn elements(&mut self, limits: &Limits, len: usize) -> Result<(), Error> {
+        self.elements = self.elements.saturating_add(len);
+        check(self.elements, limits.max_elements, Limit::Elements)
+    }
+    /// Accounts for a string or binary of `len` bytes
+    pub(crate) const fn alloc_bytes(&mut self, limits: &Limits, len: usize) -> Result<(), Error> {
+        self.alloc_bytes = self.alloc_bytes.saturating_add(len);
+        check(self.alloc_bytes, limits.max_alloc_bytes, Limit::AllocBytes)
+    }
+    /// Accounts for the item with `marker` and the length `len` read after it, for input that is copied as a whole
+    /// before it is deserialized
+    #[cfg(feature = "std")]
+    pub(crate) fn item(&mut self, limits: &Limits, marker: Marker, len: usize) -> Result<(), Error> {
+        match marker {
+            Marker::FixStr(n) => self.item(limits, Marker::Str8, n as usize),
+            Marker::Str8 | Marker::Str16 | Marker::Str32 => {
+                check(len, limits.max_str_len, Limit::StrLen)?;
+                self.alloc_bytes(limits, len)
+            }
+            Marker::Bin8 | Marker::Bin16 | Marker::Bin32 => {
+                check(len, limits.max_bin_len, Limit::BinLen)?;
+                self.alloc_bytes(limits, len)
+            }
+            Marker::FixArray(n) => self.elements(limits, n as usize),
+            Marker::Array16 | Marker::Array32 => self.elements(limits, len),
+            Marker::FixMap(n) => self.item(limits, Marker::Map16, n as usize),
+            Marker::Map16 | Marker::Map32 => {
+                check(len, limits.max_map_entries, Limit::MapEntries)?;
+                self.elements(limits, len)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+const fn check(value: usize, max: usize, limit: Limit) -> Result<(), Error> {
+    if value > max {
+        return Err(Error::LimitExceeded(limit));
+    }
+    Ok(())
+}
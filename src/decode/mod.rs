@@ -1,5 +1,26 @@
+mod canonical;
+mod limits;
+mod layout;
+#[cfg(feature = "serde")]
+mod push;
 #[cfg(feature = "serde")]
 mod serde;
+#[cfg(all(feature = "serde", feature = "std"))]
+mod reader;
+#[cfg(feature = "serde")]
+mod stream;
+
+pub use canonical::{is_canonical, MAX_CANONICAL_DEPTH};
+pub use limits::{Limit, Limits};
+
+#[cfg(feature = "serde")]
+pub use self::serde::{DefaultConfig, DeserializerConfig, DeserializerOptions, DEFAULT_MAX_DEPTH};
+#[cfg(all(feature = "serde", feature = "std"))]
+pub use reader::{from_reader, from_reader_with_config};
+#[cfg(feature = "serde")]
+pub use push::{Progress, PushDecoder};
+#[cfg(feature = "serde")]
+pub use stream::StreamDeserializer;
 
 use crate::marker::Marker;
 
@@ -33,14 +54,65 @@ pub enum Error {
     InvalidMapType,
     InvalidNewTypeLength,
     InvalidUtf8(core::str::Utf8Error),
+    /// The buffer holds more bytes after the deserialized value.
+    TrailingBytes,
+    /// An array or map holds more elements than were deserialized from it, e.g. an array of three values for a
+    /// struct with two fields.
+    TrailingElements,
+    /// Arrays and maps are nested deeper than [`DeserializerConfig::max_depth`] allows.
+    DepthLimitExceeded,
+    /// The value needs more resources than its [`Limits`] allow.
+    LimitExceeded(Limit),
+    /// The underlying [`std::io::Read`] failed.
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
 }
 
 #[cfg(feature = "serde")]
 // #[inline(never)]
 pub fn from_slice<'a, T: ::serde::de::Deserialize<'a>>(buf: &'a [u8]) -> Result<T, Error> {
-    let mut de = serde::Deserializer::new(buf);
+    from_slice_with_config(buf, DefaultConfig)
+}
+
+/// Like [`from_slice`], with the given deserializer options
+#[cfg(feature = "serde")]
+pub fn from_slice_with_config<'a, T: ::serde::de::Deserialize<'a>, C: DeserializerConfig>(buf: &'a [u8], config: C) -> Result<T, Error> {
+    from_slice_partial_with_config(buf, config).map(|(value, _)| value)
+}
+
+/// Deserializes the value at the start of `buf`
+///
+/// Returns the value and the number of bytes it took, anything after it is left alone.
+#[cfg(feature = "serde")]
+pub fn from_slice_partial<'a, T: ::serde::de::Deserialize<'a>>(buf: &'a [u8]) -> Result<(T, usize), Error> {
+    from_slice_partial_with_config(buf, DefaultConfig)
+}
+
+/// Like [`from_slice_partial`], with the given deserializer options
+#[cfg(feature = "serde")]
+pub fn from_slice_partial_with_config<'a, T: ::serde::de::Deserialize<'a>, C: DeserializerConfig>(
+    buf: &'a [u8],
+    config: C,
+) -> Result<(T, usize), Error> {
+    let mut de = serde::Deserializer::new(buf, config);
     let value = ::serde::de::Deserialize::deserialize(&mut de)?;
 
+    Ok((value, de.position()))
+}
+
+/// Like [`from_slice`], but fails with [`Error::TrailingBytes`] unless the value takes the whole buffer
+#[cfg(feature = "serde")]
+pub fn from_slice_strict<'a, T: ::serde::de::Deserialize<'a>>(buf: &'a [u8]) -> Result<T, Error> {
+    from_slice_strict_with_config(buf, DefaultConfig)
+}
+
+/// Like [`from_slice_strict`], with the given deserializer options
+#[cfg(feature = "serde")]
+pub fn from_slice_strict_with_config<'a, T: ::serde::de::Deserialize<'a>, C: DeserializerConfig>(buf: &'a [u8], config: C) -> Result<T, Error> {
+    let (value, len) = from_slice_partial_with_config(buf, config)?;
+    if len < buf.len() {
+        return Err(Error::TrailingBytes);
+    }
     Ok(value)
 }
 
@@ -104,6 +176,20 @@ impl DeserializeFromSlice for Option<i64> {
         Ok(n)
     }
 }
+impl DeserializeFromSlice for Option<u128> {
+    fn from_slice(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let (v, n) = read_u128(buf)?;
+        *self = Some(v);
+        Ok(n)
+    }
+}
+impl DeserializeFromSlice for Option<i128> {
+    fn from_slice(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let (v, n) = read_i128(buf)?;
+        *self = Some(v);
+        Ok(n)
+    }
+}
 
 pub fn read_raw_u8(buf: &[u8]) -> Result<(u8, &[u8]), Error> {
     buf.split_first().map(|(&x, rest)| (x, rest)).ok_or(Error::EndOfBuffer(Marker::U8))
@@ -305,10 +391,41 @@ pub fn read_i64<B: ByteSlice>(buf: B) -> Result<(i64, usize), Error> {
                 Err(Error::EndOfBuffer(Marker::I64))
             }
         }
-        _ => Err(Error::EndOfBuffer(Marker::I64)),
+        _ => Err(Error::InvalidType),
     }
 }
 
+/// Reads any integer marker, or a bin of 16 big-endian bytes as written by
+/// [`serialize_u128`](crate::encode::serialize_u128)
+pub fn read_u128<B: ByteSlice>(buf: B) -> Result<(u128, usize), Error> {
+    match buf.first().map(|&b| Marker::from(b)) {
+        Some(Marker::Bin8 | Marker::Bin16 | Marker::Bin32) => {
+            let (bytes, len) = read_int128_bytes(buf)?;
+            Ok((u128::from_be_bytes(bytes), len))
+        }
+        _ => read_u64(buf).map(|(v, len)| (u128::from(v), len)),
+    }
+}
+
+/// Reads any integer marker, or a bin of 16 big-endian bytes in two's complement as written by
+/// [`serialize_i128`](crate::encode::serialize_i128)
+pub fn read_i128<B: ByteSlice>(buf: B) -> Result<(i128, usize), Error> {
+    match buf.first().map(|&b| Marker::from(b)) {
+        Some(Marker::Bin8 | Marker::Bin16 | Marker::Bin32) => {
+            let (bytes, len) = read_int128_bytes(buf)?;
+            Ok((i128::from_be_bytes(bytes), len))
+        }
+        Some(Marker::U64) => read_u64(buf).map(|(v, len)| (i128::from(v), len)),
+        _ => read_i64(buf).map(|(v, len)| (i128::from(v), len)),
+    }
+}
+
+fn read_int128_bytes<B: ByteSlice>(buf: B) -> Result<([u8; 16], usize), Error> {
+    let (bytes, len) = read_bin(buf)?;
+    let bytes = <[u8; 16]>::try_from(&bytes[..]).map_err(|_| Error::InvalidBinType)?;
+    Ok((bytes, len))
+}
+
 pub fn read_f32<B: ByteSlice>(buf: B) -> Result<(f32, usize), Error> {
     if buf.len() == 0 {
         return Err(Error::EndOfBuffer(Marker::F32));
@@ -323,7 +440,12 @@ pub fn read_f32<B: ByteSlice>(buf: B) -> Result<(f32, usize), Error> {
                 Err(Error::EndOfBuffer(Marker::F32))
             }
         }
-        _ => Err(Error::EndOfBuffer(Marker::F32)),
+        // integral values may be written as integers
+        Marker::U64 => read_u64(buf).map(|(v, len)| (v as f32, len)),
+        Marker::FixPos(_) | Marker::FixNeg(_) | Marker::U8 | Marker::U16 | Marker::U32 | Marker::I8 | Marker::I16 | Marker::I32 | Marker::I64 => {
+            read_i64(buf).map(|(v, len)| (v as f32, len))
+        }
+        _ => Err(Error::InvalidType),
     }
 }
 pub fn read_f64<B: ByteSlice>(buf: B) -> Result<(f64, usize), Error> {
@@ -349,8 +471,12 @@ pub fn read_f64<B: ByteSlice>(buf: B) -> Result<(f64, usize), Error> {
                 Err(Error::EndOfBuffer(Marker::F64))
             }
         }
-        Marker::FixPos(v) => Ok((f64::from(v), 1)),
-        _ => Err(Error::EndOfBuffer(Marker::F64)),
+        // integral values may be written as integers
+        Marker::U64 => read_u64(buf).map(|(v, len)| (v as f64, len)),
+        Marker::FixPos(_) | Marker::FixNeg(_) | Marker::U8 | Marker::U16 | Marker::U32 | Marker::I8 | Marker::I16 | Marker::I32 | Marker::I64 => {
+            read_i64(buf).map(|(v, len)| (v as f64, len))
+        }
+        _ => Err(Error::InvalidType),
     }
 }
 
@@ -573,92 +699,14 @@ pub fn read_map_len<B: ByteSlice>(buf: B) -> Result<(usize, usize), Error> {
     }
 }
 
+/// Skips the value at the start of `buf`, returning its length
+///
+/// Nested arrays and maps are walked without recursion, so deeply nested input can't exhaust the stack. If `buf` ends
+/// inside the value, the error holds the marker of the item that is cut off.
 pub fn skip_any<B: ByteSlice>(buf: B) -> Result<((), usize), Error> {
+    let buf: &[u8] = &buf;
     if buf.is_empty() {
         return Ok(((), 0));
     }
-    let marker = Marker::from_u8(buf[0]);
-    let n = match marker {
-        Marker::FixPos(_) => 1,
-        Marker::U8 => 2,
-        Marker::U16 => 3,
-        Marker::U32 => 5,
-        Marker::U64 => 9,
-        Marker::FixNeg(_) => 1,
-        Marker::I8 => 2,
-        Marker::I16 => 3,
-        Marker::I32 => 5,
-        Marker::I64 => 9,
-
-        Marker::F32 => 5,
-        Marker::F64 => 9,
-
-        Marker::Null | Marker::True | Marker::False | Marker::Reserved => 1,
-
-        Marker::FixStr(n) => n as usize + 1,
-        Marker::Str8 | Marker::Bin8 => {
-            if buf.len() < 2 {
-                return Err(Error::EndOfBuffer(Marker::Str8));
-            }
-            2 + buf[1] as usize
-        }
-        Marker::Str16 | Marker::Bin16 => {
-            if buf.len() < 3 {
-                return Err(Error::EndOfBuffer(Marker::Str16));
-            }
-            3 + BigEndian::read_u16(&buf[1..3]) as usize
-        }
-        Marker::Str32 | Marker::Bin32 => {
-            if buf.len() < 5 {
-                return Err(Error::EndOfBuffer(Marker::Str32));
-            }
-            5 + BigEndian::read_u32(&buf[1..5]) as usize
-        }
-
-        Marker::FixArray(_) | Marker::Array16 | Marker::Array32 => {
-            let (len, n) = read_array_len(&buf[..])?;
-            let mut n = n;
-            for _ in 0..len {
-                //TODO: May overflow stack on embedded systems. Maybe add some kind of safeguard to limit recursion depth
-                n += skip_any(&buf[n..])?.1;
-            }
-            n
-        }
-        Marker::FixMap(_) | Marker::Map16 | Marker::Map32 => {
-            let (len, n) = read_map_len(&buf[..])?;
-            let mut n = n;
-            for _ in 0..len * 2 {
-                //TODO: May overflow stack on embedded systems. Maybe add some kind of safeguard to limit recursion depth
-                n += skip_any(&buf[n..])?.1;
-            }
-            n
-        }
-        Marker::FixExt1 => 3,
-        Marker::FixExt2 => 4,
-        Marker::FixExt4 => 6,
-        Marker::FixExt8 => 10,
-        Marker::FixExt16 => 18,
-        Marker::Ext8 => {
-            if buf.len() < 2 {
-                return Err(Error::EndOfBuffer(Marker::Ext8));
-            }
-            3 + buf[1] as usize
-        }
-        Marker::Ext16 => {
-            if buf.len() < 3 {
-                return Err(Error::EndOfBuffer(Marker::Ext16));
-            }
-            4 + BigEndian::read_u16(&buf[1..3]) as usize
-        }
-        Marker::Ext32 => {
-            if buf.len() < 5 {
-                return Err(Error::EndOfBuffer(Marker::Ext32));
-            }
-            6 + BigEndian::read_u32(&buf[1..5]) as usize
-        }
-    };
-    if buf.len() < n {
-        return Err(Error::EndOfBuffer(Marker::Ext32));
-    }
-    Ok(((), n))
+    layout::value_len(buf).map(|len| ((), len)).map_err(|truncated| Error::EndOfBuffer(truncated.marker))
 }
@@ -0,0 +1,72 @@
+use super::{from_slice_with_config, layout, DefaultConfig, DeserializerConfig, Error};
+
+/// Result of [`PushDecoder::next_value`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Progress<T> {
+    /// A complete value was decoded and removed from the buffer
+    Value(T),
+    /// The buffered bytes end inside a value, at least this many more bytes have to be pushed
+    NeedMore(usize),
+}
+
+/// Decoder fed with input as it arrives, e.g. fragments of network frames
+///
+/// The bytes are collected in the buffer given to [`PushDecoder::new`] until a value is complete. A value larger than
+/// the buffer can't be decoded, [`next_value`](Self::next_value) fails as soon as its headers show that.
+pub struct PushDecoder<'a, C = DefaultConfig> {
+    buf: &'a mut [u8],
+    len: usize,
+    config: C,
+}
+
+impl<'a> PushDecoder<'a> {
+    pub const fn new(buf: &'a mut [u8]) -> Self {
+        Self::with_config(buf, DefaultConfig)
+    }
+}
+
+impl<'a, C: DeserializerConfig> PushDecoder<'a, C> {
+    /// Like [`PushDecoder::new`], with the given deserializer options
+    pub const fn with_config(buf: &'a mut [u8], config: C) -> Self {
+        PushDecoder { buf, len: 0, config }
+    }
+
+    /// Appends as much of `data` as fits into the buffer
+    ///
+    /// Returns the number of bytes taken, the rest has to be pushed again once values were taken out with
+    /// [`next_value`](Self::next_value).
+    pub fn push(&mut self, data: &[u8]) -> usize {
+        let n = data.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&data[..n]);
+        self.len += n;
+        n
+    }
+
+    /// Decodes the next value if all of its bytes were pushed
+    ///
+    /// # Errors
+    ///
+    /// Fails if the complete value can't be deserialized as `T`. Its bytes are dropped anyway, so decoding continues
+    /// with the value after it.
+    ///
+    /// Fails with [`Error::EndOfBuffer`] if the value doesn't fit into the buffer. Its bytes stay buffered then, the
+    /// stream can't be decoded any further.
+    pub fn next_value<T: ::serde::de::DeserializeOwned>(&mut self) -> Result<Progress<T>, Error> {
+        let len = match layout::value_len(&self.buf[..self.len]) {
+            Ok(len) => len,
+            Err(truncated) if self.len.saturating_add(truncated.missing) > self.buf.len() => {
+                return Err(Error::EndOfBuffer(truncated.marker))
+            }
+            Err(truncated) => return Ok(Progress::NeedMore(truncated.missing)),
+        };
+        let value = from_slice_with_config(&self.buf[..len], self.config);
+        self.buf.copy_within(len..self.len, 0);
+        self.len -= len;
+        value.map(Progress::Value)
+    }
+
+    /// The bytes pushed but not decoded yet
+    pub fn buffered(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
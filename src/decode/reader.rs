@@ -0,0 +1,76 @@
+use std::io::Read;
+use std::vec::Vec;
+
+use super::limits::Budget;
+use super::{from_slice_with_config, layout, DefaultConfig, DeserializerConfig, Error};
+use crate::marker::Marker;
+
+/// Deserializes one value from `reader`
+///
+/// Exactly the bytes of the value are consumed, so more values following it can be read by further calls. The value
+/// is collected in memory before it is deserialized, the depth and [`Limits`](super::Limits) of the configuration
+/// are checked against its headers while it is read, so endless or oversized input fails early. Every string and
+/// binary is counted towards [`Limits::max_alloc_bytes`](super::Limits::max_alloc_bytes), they are all copied.
+/// Each header and each payload is a single read, a [`BufReader`](std::io::BufReader) still helps with unbuffered
+/// sources and values of many small items.
+pub fn from_reader<R: Read, T: ::serde::de::DeserializeOwned>(reader: R) -> Result<T, Error> {
+    from_reader_with_config(reader, DefaultConfig)
+}
+
+/// Like [`from_reader`], with the given deserializer options
+pub fn from_reader_with_config<R: Read, T: ::serde::de::DeserializeOwned, C: DeserializerConfig>(
+    mut reader: R,
+    config: C,
+) -> Result<T, Error> {
+    let mut buf = Vec::new();
+    read_value(&mut reader, &mut buf, &config)?;
+    from_slice_with_config(&buf, config)
+}
+
+/// Appends the bytes of the next value of `reader` to `buf`
+fn read_value<R: Read, C: DeserializerConfig>(reader: &mut R, buf: &mut Vec<u8>, config: &C) -> Result<(), Error> {
+    let limits = config.limits();
+    let mut budget = Budget::new();
+    // values still to be read in each of the open arrays and maps, innermost last
+    let mut levels: Vec<usize> = Vec::new();
+    loop {
+        if let Some(remaining) = levels.last_mut() {
+            *remaining -= 1;
+        }
+        let start = buf.len();
+        read_exact(reader, buf, 1, Marker::Reserved)?;
+        let marker = Marker::from_u8(buf[start]);
+        read_exact(reader, buf, layout::len_bytes(marker), marker)?;
+        let len = layout::read_len(marker, &buf[start..]);
+        budget.item(&limits, marker, len)?;
+        let (data_len, nested) = layout::body(marker, len);
+        if matches!(
+            marker,
+            Marker::FixArray(_) | Marker::Array16 | Marker::Array32 | Marker::FixMap(_) | Marker::Map16 | Marker::Map32
+        ) {
+            if levels.len() >= config.max_depth() {
+                return Err(Error::DepthLimitExceeded);
+            }
+            levels.push(nested);
+        }
+        read_exact(reader, buf, data_len, marker)?;
+        while levels.last() == Some(&0) {
+            levels.pop();
+        }
+        if levels.is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+/// Appends the next `n` bytes of `reader` to `buf`, growing it only as far as data arrives
+fn read_exact<R: Read>(reader: &mut R, buf: &mut Vec<u8>, n: usize, marker: Marker) -> Result<(), Error> {
+    if n == 0 {
+        return Ok(());
+    }
+    let read = reader.by_ref().take(n as u64).read_to_end(buf).map_err(Error::Io)?;
+    if read < n {
+        return Err(Error::EndOfBuffer(marker));
+    }
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use crate::decode::Limits;
+use crate::encode::serde::StructRepr;
+
+/// Nesting depth [`DeserializerConfig::max_depth`] allows by default
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// Options of the serde deserializer
+///
+/// Like [`SerializerConfig`](crate::encode::serde::SerializerConfig), constant implementations are resolved at
+/// compile time. [`DeserializerOptions`] allows choosing them at runtime.
+pub trait DeserializerConfig: Copy {
+    /// Representation structs must be encoded in, `None` (the default) accepts both maps and arrays
+    #[inline(always)]
+    fn struct_repr(&self) -> Option<StructRepr> {
+        None
+    }
+    /// Largest number of arrays and maps nested in each other, [`DEFAULT_MAX_DEPTH`] by default
+    ///
+    /// Every level takes stack space while it is deserialized, the limit keeps crafted input from exhausting it.
+    /// Deeper input fails with [`Error::DepthLimitExceeded`](crate::decode::Error::DepthLimitExceeded).
+    #[inline(always)]
+    fn max_depth(&self) -> usize {
+        DEFAULT_MAX_DEPTH
+    }
+    /// Resource limits of the decoded value, unbounded by default
+    #[inline(always)]
+    fn limits(&self) -> Limits {
+        Limits::new()
+    }
+}
+
+/// Accepts any supported representation
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultConfig;
+
+impl DeserializerConfig for DefaultConfig {}
+
+/// Deserializer options chosen at runtime
+#[derive(Clone, Copy, Debug)]
+pub struct DeserializerOptions {
+    struct_repr: Option<StructRepr>,
+    max_depth: usize,
+    limits: Limits,
+}
+
+impl DeserializerOptions {
+    /// Options with the same behaviour as [`DefaultConfig`]
+    pub const fn new() -> Self {
+        DeserializerOptions {
+            struct_repr: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            limits: Limits::new(),
+        }
+    }
+    /// See [`DeserializerConfig::struct_repr`]
+    pub const fn struct_repr(mut self, repr: Option<StructRepr>) -> Self {
+        self.struct_repr = repr;
+        self
+    }
+    /// See [`DeserializerConfig::max_depth`]
+    pub const fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+    /// See [`DeserializerConfig::limits`]
+    pub const fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+impl Default for DeserializerOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeserializerConfig for DeserializerOptions {
+    #[inline]
+    fn struct_repr(&self) -> Option<StructRepr> {
+        self.struct_repr
+    }
+    #[inline]
+    fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+    #[inline]
+    fn limits(&self) -> Limits {
+        self.limits
+    }
+}
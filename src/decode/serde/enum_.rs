@@ -1,18 +1,24 @@
 use serde::de;
 
-use super::{print_debug, Deserializer, Error};
+use super::{print_debug, Deserializer, DeserializerConfig, Error};
+use crate::encode::serde::StructRepr;
+use crate::marker::Marker;
 
-pub(crate) struct UnitVariantAccess<'a, 'b> {
-    de: &'a mut Deserializer<'b>,
+/// Access to an externally tagged enum, either the bare variant followed by its payload or a map of one entry
+/// `{ variant: payload }`
+pub(crate) struct EnumAccess<'a, 'b, C> {
+    de: &'a mut Deserializer<'b, C>,
+    /// The variant is the key of a map, a unit variant still has a value then
+    in_map: bool,
 }
 
-impl<'a, 'b> UnitVariantAccess<'a, 'b> {
-    pub(crate) fn new(de: &'a mut Deserializer<'b>) -> Self {
-        UnitVariantAccess { de }
+impl<'a, 'b, C> EnumAccess<'a, 'b, C> {
+    pub(crate) const fn new(de: &'a mut Deserializer<'b, C>, in_map: bool) -> Self {
+        EnumAccess { de, in_map }
     }
 }
 
-impl<'a, 'de> de::EnumAccess<'de> for UnitVariantAccess<'a, 'de> {
+impl<'a, 'de, C: DeserializerConfig> de::EnumAccess<'de> for EnumAccess<'a, 'de, C> {
     type Error = Error;
     type Variant = Self;
 
@@ -20,17 +26,25 @@ impl<'a, 'de> de::EnumAccess<'de> for UnitVariantAccess<'a, 'de> {
     where
         V: de::DeserializeSeed<'de>,
     {
-        print_debug::<V>("UnitVariantAccess::", "variant_seed", self.de);
+        print_debug::<V>("EnumAccess::", "variant_seed", self.de);
         let variant = seed.deserialize(&mut *self.de)?;
         Ok((variant, self))
     }
 }
 
-impl<'de, 'a> de::VariantAccess<'de> for UnitVariantAccess<'a, 'de> {
+impl<'de, 'a, C: DeserializerConfig> de::VariantAccess<'de> for EnumAccess<'a, 'de, C> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<(), Self::Error> {
-        print_debug::<()>("UnitVariantAccess::", "unit_variant", self.de);
+        print_debug::<()>("EnumAccess::", "unit_variant", self.de);
+        if self.in_map {
+            // `{ variant: nil }` or `{ variant: [] }`
+            match self.de.peek() {
+                Some(Marker::Null | Marker::FixArray(0)) => self.de.eat_byte(),
+                Some(_) => return Err(Error::InvalidType),
+                None => return Err(Error::EndOfBuffer(Marker::Null)),
+            }
+        }
         Ok(())
     }
 
@@ -38,7 +52,7 @@ impl<'de, 'a> de::VariantAccess<'de> for UnitVariantAccess<'a, 'de> {
     where
         T: de::DeserializeSeed<'de>,
     {
-        print_debug::<T>("UnitVariantAccess::", "newtype_variant_seed", self.de);
+        print_debug::<T>("EnumAccess::", "newtype_variant_seed", self.de);
         seed.deserialize(self.de)
     }
 
@@ -46,26 +60,36 @@ impl<'de, 'a> de::VariantAccess<'de> for UnitVariantAccess<'a, 'de> {
     where
         V: de::Visitor<'de>,
     {
-        print_debug::<V>("UnitVariantAccess::", "tuple_variant", self.de);
-        let (len, header_len) = crate::decode::read_array_len(&self.de.slice[self.de.index..])?;
+        print_debug::<V>("EnumAccess::", "tuple_variant", self.de);
+        let (len, header_len) = self.de.config.limits().read_array_len(&self.de.slice[self.de.index..])?;
         if len != v_len {
             return Err(Error::OutOfBounds);
         }
         self.de.index += header_len;
-        visitor.visit_seq(super::SeqAccess::new(&mut *self.de, len))
+        self.de.visit_seq(len, visitor)
     }
 
     fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        print_debug::<V>("UnitVariantAccess::", "struct_variant", self.de);
-        let (len, header_len) = crate::decode::read_array_len(&self.de.slice[self.de.index..])?;
-        self.de.index += header_len;
-        if len != fields.len() {
-            return Err(Error::OutOfBounds);
+        print_debug::<V>("EnumAccess::", "struct_variant", self.de);
+        // like structs, the fields can be encoded as an array of their values or as a map
+        match (self.de.peek(), self.de.config.struct_repr()) {
+            (Some(Marker::FixMap(_) | Marker::Map16 | Marker::Map32), None | Some(StructRepr::Map)) => {
+                let (len, header_len) = self.de.config.limits().read_map_len(&self.de.slice[self.de.index..])?;
+                self.de.index += header_len;
+                self.de.visit_map(len, visitor)
+            }
+            (_, Some(StructRepr::Map)) => Err(Error::InvalidMapType),
+            _ => {
+                let (len, header_len) = self.de.config.limits().read_array_len(&self.de.slice[self.de.index..])?;
+                if len != fields.len() {
+                    return Err(Error::OutOfBounds);
+                }
+                self.de.index += header_len;
+                self.de.visit_seq(len, visitor)
+            }
         }
-        visitor.visit_seq(super::SeqAccess::new(&mut *self.de, len))
-        // visitor.visit_map(super::MapAccess::new(&mut *self.de, fields.len()))
     }
 }
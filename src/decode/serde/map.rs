@@ -1,19 +1,27 @@
 use serde::de;
 
-use super::{print_debug, Deserializer, Error};
+use super::{print_debug, Deserializer, DeserializerConfig, Error};
 
-pub struct MapAccess<'a, 'b> {
-    de: &'a mut Deserializer<'b>,
+pub struct MapAccess<'a, 'b, C> {
+    de: &'a mut Deserializer<'b, C>,
     count: usize,
 }
 
-impl<'a, 'b> MapAccess<'a, 'b> {
-    pub(crate) fn new(de: &'a mut Deserializer<'b>, count: usize) -> Self {
+impl<'a, 'b, C> MapAccess<'a, 'b, C> {
+    pub(crate) const fn new(de: &'a mut Deserializer<'b, C>, count: usize) -> Self {
         MapAccess { de, count: count * 2 }
     }
+
+    /// Fails if the visitor didn't deserialize all entries
+    pub(crate) const fn end(&self) -> Result<(), Error> {
+        if self.count > 0 {
+            return Err(Error::TrailingElements);
+        }
+        Ok(())
+    }
 }
 
-impl<'a, 'de> de::MapAccess<'de> for MapAccess<'a, 'de> {
+impl<'a, 'de, C: DeserializerConfig> de::MapAccess<'de> for MapAccess<'a, 'de, C> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
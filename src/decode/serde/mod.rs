@@ -1,20 +1,25 @@
 use crate::marker::Marker;
 use core::fmt;
 use paste::paste;
-use serde::de::{self, Visitor};
+use serde::de::{self, Deserializer as _, Visitor};
 
-use self::{enum_::UnitVariantAccess, map::MapAccess, seq::SeqAccess};
+use self::{enum_::EnumAccess, map::MapAccess, seq::SeqAccess};
 
+mod config;
 mod enum_;
 mod map;
 mod seq;
 
-use super::Error;
+pub use config::{DefaultConfig, DeserializerConfig, DeserializerOptions, DEFAULT_MAX_DEPTH};
+
+use super::limits::Budget;
+use super::{Error, Limit};
+use crate::encode::serde::StructRepr;
 
 type Result<T> = core::result::Result<T, Error>;
 
 #[cfg(test)]
-fn print_debug<T>(prefix: &str, function_name: &str, de: &Deserializer) {
+fn print_debug<T>(prefix: &str, function_name: &str, de: &Deserializer<'_, impl DeserializerConfig>) {
     #[cfg(not(feature = "std"))]
     extern crate std;
     #[cfg(not(feature = "std"))]
@@ -29,7 +34,7 @@ fn print_debug<T>(prefix: &str, function_name: &str, de: &Deserializer) {
 }
 
 #[cfg(test)]
-fn print_debug_value<T, V: core::fmt::Debug>(function_name: &str, de: &Deserializer, value: &V) {
+fn print_debug_value<T, V: core::fmt::Debug>(function_name: &str, de: &Deserializer<'_, impl DeserializerConfig>, value: &V) {
     #[cfg(not(feature = "std"))]
     extern crate std;
     #[cfg(not(feature = "std"))]
@@ -45,27 +50,185 @@ fn print_debug_value<T, V: core::fmt::Debug>(function_name: &str, de: &Deseriali
 
 #[cfg(not(test))]
 #[allow(clippy::missing_const_for_fn)]
-fn print_debug<T>(_prefix: &str, _function_name: &str, _de: &Deserializer) {}
+fn print_debug<T>(_prefix: &str, _function_name: &str, _de: &Deserializer<'_, impl DeserializerConfig>) {}
 #[cfg(not(test))]
 #[allow(clippy::missing_const_for_fn)]
-fn print_debug_value<T, V: core::fmt::Debug>(_function_name: &str, _de: &Deserializer, _value: &V) {}
+fn print_debug_value<T, V: core::fmt::Debug>(_function_name: &str, _de: &Deserializer<'_, impl DeserializerConfig>, _value: &V) {}
 
-pub(crate) struct Deserializer<'b> {
+pub(crate) struct Deserializer<'b, C = DefaultConfig> {
     slice: &'b [u8],
     index: usize,
     state: State,
+    config: C,
+    depth: usize,
+    budget: Budget,
 }
 
-impl<'a> Deserializer<'a> {
-    pub const fn new(slice: &'a [u8]) -> Deserializer<'_> {
+impl<'a, C: DeserializerConfig> Deserializer<'a, C> {
+    pub const fn new(slice: &'a [u8], config: C) -> Deserializer<'a, C> {
         Deserializer {
             slice,
             index: 0,
             state: State::Normal,
+            config,
+            depth: 0,
+            budget: Budget::new(),
+        }
+    }
+
+    /// Number of bytes consumed so far
+    pub(crate) const fn position(&self) -> usize {
+        self.index
+    }
+
+    /// Enters a nested array or map, failing if that exceeds the depth limit
+    fn enter(&mut self) -> Result<()> {
+        if self.depth >= self.config.max_depth() {
+            return Err(Error::DepthLimitExceeded);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Deserializes the content of an array with `len` elements, the header is already consumed
+    pub(crate) fn visit_seq<V: Visitor<'a>>(&mut self, len: usize, visitor: V) -> Result<V::Value> {
+        self.budget.elements(&self.config.limits(), len)?;
+        self.enter()?;
+        let mut seq = SeqAccess::new(self, len);
+        let value = visitor.visit_seq(&mut seq).and_then(|value| seq.end().map(|()| value));
+        self.depth -= 1;
+        value
+    }
+
+    /// Deserializes the content of a map with `len` entries, the header is already consumed
+    pub(crate) fn visit_map<V: Visitor<'a>>(&mut self, len: usize, visitor: V) -> Result<V::Value> {
+        self.budget.elements(&self.config.limits(), len)?;
+        self.enter()?;
+        let mut map = MapAccess::new(self, len);
+        let value = visitor.visit_map(&mut map).and_then(|value| map.end().map(|()| value));
+        self.depth -= 1;
+        value
+    }
+
+    /// Visits the ext value at the current position as a sequence of its type and its data
+    #[cfg(feature = "ext")]
+    fn deserialize_ext<V: Visitor<'a>>(&mut self, visitor: V) -> Result<V::Value> {
+        let (header_len, data_len) = crate::ext::read_ext_len(&self.slice[self.index..])?;
+        self.index += header_len - 1; // move forward minus 1 byte for the ext type (header_len includes the type byte)
+        self.state = State::ExtType(data_len);
+        let mut seq = SeqAccess::new(self, 2);
+        let value = visitor.visit_seq(&mut seq).and_then(|value| seq.end().map(|()| value));
+        // the visitor may have stopped before the data
+        self.state = State::Normal;
+        value
+    }
+
+    /// Deserializes the value at the current position by its marker, `owned` if the visitor copies strings and
+    /// binaries
+    fn visit_any<V: Visitor<'a>>(&mut self, visitor: V, owned: bool) -> Result<V::Value> {
+        // the parts of an ext value aren't encoded with markers
+        match self.state {
+            State::Normal => {}
+            #[cfg(feature = "ext")]
+            State::ExtType(_) => return self.deserialize_i8(visitor),
+            #[cfg(feature = "ext")]
+            State::Ext(_) => return self.visit_bytes(visitor, owned),
         }
+        let marker = self.peek().ok_or(Error::EndOfBuffer(Marker::Reserved))?;
+        match marker {
+            Marker::Null => {
+                self.eat_byte();
+                visitor.visit_unit()
+            }
+            Marker::FixPos(_) => self.deserialize_u8(visitor),
+            Marker::FixNeg(_) => self.deserialize_i8(visitor),
+            Marker::FixMap(_) => self.deserialize_map(visitor),
+            Marker::Map16 => self.deserialize_map(visitor),
+            Marker::Map32 => self.deserialize_map(visitor),
+            Marker::FixArray(_) => self.deserialize_seq(visitor),
+            Marker::Array16 => self.deserialize_seq(visitor),
+            Marker::Array32 => self.deserialize_seq(visitor),
+            Marker::Str8 => self.visit_str(visitor, owned),
+            Marker::Str16 => self.visit_str(visitor, owned),
+            Marker::Str32 => self.visit_str(visitor, owned),
+            Marker::Bin8 => self.visit_bytes(visitor, owned),
+            Marker::Bin16 => self.visit_bytes(visitor, owned),
+            Marker::Bin32 => self.visit_bytes(visitor, owned),
+            Marker::FixStr(_) => self.visit_str(visitor, owned),
+            Marker::F32 => self.deserialize_f32(visitor),
+            Marker::F64 => self.deserialize_f64(visitor),
+            Marker::I16 => self.deserialize_i16(visitor),
+            Marker::I32 => self.deserialize_i32(visitor),
+            Marker::I64 => self.deserialize_i64(visitor),
+            Marker::I8 => self.deserialize_i8(visitor),
+            Marker::U16 => self.deserialize_u16(visitor),
+            Marker::U32 => self.deserialize_u32(visitor),
+            Marker::U64 => self.deserialize_u64(visitor),
+            Marker::U8 => self.deserialize_u8(visitor),
+            Marker::True => self.deserialize_bool(visitor),
+            Marker::False => self.deserialize_bool(visitor),
+            #[cfg(feature = "ext")]
+            Marker::FixExt1
+            | Marker::FixExt2
+            | Marker::FixExt4
+            | Marker::FixExt8
+            | Marker::FixExt16
+            | Marker::Ext8
+            | Marker::Ext16
+            | Marker::Ext32 => self.deserialize_ext(visitor),
+            // without ext support the value can only be skipped
+            #[cfg(not(feature = "ext"))]
+            Marker::FixExt1
+            | Marker::FixExt2
+            | Marker::FixExt4
+            | Marker::FixExt8
+            | Marker::FixExt16
+            | Marker::Ext8
+            | Marker::Ext16
+            | Marker::Ext32 => {
+                print_debug::<V>("Deserializer::deserialize_", "any", self);
+                let (_, n) = super::skip_any(&self.slice[self.index..])?;
+                self.index += n;
+                visitor.visit_unit()
+            }
+            Marker::Reserved => Err(Error::InvalidType),
+        }
+    }
+
+    /// Deserializes the string at the current position, charging it to the budget if the visitor copies it
+    fn visit_str<V: Visitor<'a>>(&mut self, visitor: V, owned: bool) -> Result<V::Value> {
+        let limits = self.config.limits();
+        let (s, len) = limits.read_str(&self.slice[self.index..])?;
+        if owned {
+            self.budget.alloc_bytes(&limits, s.len())?;
+        }
+        self.index += len;
+        visitor.visit_borrowed_str(s)
+    }
+
+    /// Deserializes the binary or ext data at the current position, charging it to the budget if the visitor copies
+    /// it
+    fn visit_bytes<V: Visitor<'a>>(&mut self, visitor: V, owned: bool) -> Result<V::Value> {
+        let limits = self.config.limits();
+        let (value, len) = match self.state {
+            State::Normal => limits.read_bin(&self.slice[self.index..])?,
+            // read the ext type as raw byte and not encoded as a normal i8
+            #[cfg(feature = "ext")]
+            State::Ext(len) => {
+                self.state = State::Normal;
+                (&self.slice[self.index..self.index + len], len)
+            }
+            #[cfg(feature = "ext")]
+            State::ExtType(_) => return Err(Error::InvalidType),
+        };
+        if owned {
+            self.budget.alloc_bytes(&limits, value.len())?;
+        }
+        self.index += len;
+        visitor.visit_borrowed_bytes(value)
     }
 
-    fn eat_byte(&mut self) {
+    const fn eat_byte(&mut self) {
         self.index += 1;
     }
 
@@ -85,10 +248,15 @@ macro_rules! deserialize_primitives {
 
 enum State {
     Normal,
+    /// At the type byte of an ext value with the given data length
+    #[cfg(feature = "ext")]
+    ExtType(usize),
+    /// At the data of an ext value with the given length
+    #[cfg(feature = "ext")]
     Ext(usize),
 }
 
-impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de, C: DeserializerConfig> de::Deserializer<'de> for &mut Deserializer<'de, C> {
     type Error = Error;
 
     deserialize_primitives!(i64, i16, i32);
@@ -104,7 +272,12 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
             State::Normal => super::read_i8(&self.slice[self.index..])?,
             // read the ext type as raw byte and not encoded as a normal i8
             #[cfg(feature = "ext")]
-            State::Ext(_) => (self.slice[self.index] as i8, 1),
+            State::ExtType(len) => {
+                self.state = State::Ext(len);
+                (self.slice[self.index] as i8, 1)
+            }
+            #[cfg(feature = "ext")]
+            State::Ext(_) => return Err(Error::InvalidType),
         };
         self.index += len;
         print_debug_value::<i8, i8>("Deserializer::deserialize_i8", self, &value);
@@ -113,29 +286,17 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
     fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
         print_debug::<V>("Deserializer::deserialize_", "str", self);
-        let (s, len) = super::read_str(&self.slice[self.index..])?;
-        self.index += len;
-        visitor.visit_borrowed_str(s)
+        self.visit_str(visitor, false)
     }
 
     fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
         print_debug::<V>("Deserializer::deserialize_", "bytes", self);
-        let (value, len) = match self.state {
-            State::Normal => super::read_bin(&self.slice[self.index..])?,
-            // read the ext type as raw byte and not encoded as a normal i8
-            #[cfg(feature = "ext")]
-            State::Ext(len) => {
-                self.state = State::Normal;
-                (&self.slice[self.index..self.index + len], len)
-            }
-        };
-        self.index += len;
-        visitor.visit_borrowed_bytes(value)
+        self.visit_bytes(visitor, false)
     }
 
     fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
         print_debug::<V>("Deserializer::deserialize_", "byte_buf", self);
-        self.deserialize_bytes(visitor)
+        self.visit_bytes(visitor, true)
     }
 
     fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
@@ -152,9 +313,9 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
     fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
         print_debug::<V>("Deserializer::deserialize_", "seq", self);
-        let (len, header_len) = crate::decode::read_array_len(&self.slice[self.index..])?;
+        let (len, header_len) = self.config.limits().read_array_len(&self.slice[self.index..])?;
         self.index += header_len;
-        visitor.visit_seq(SeqAccess::new(self, len))
+        self.visit_seq(len, visitor)
     }
 
     fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
@@ -169,9 +330,9 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
     fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
         print_debug::<V>("Deserializer::deserialize_", "map", self);
-        let (len, header_len) = crate::decode::read_map_len(&self.slice[self.index..])?;
+        let (len, header_len) = self.config.limits().read_map_len(&self.slice[self.index..])?;
         self.index += header_len;
-        visitor.visit_map(MapAccess::new(self, len))
+        self.visit_map(len, visitor)
     }
 
     fn deserialize_struct<V: Visitor<'de>>(self, name: &'static str, _fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
@@ -179,8 +340,8 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         match name {
             #[cfg(feature = "ext")]
             crate::ext::TYPE_NAME | crate::timestamp::TYPE_NAME => {
-                if let Some(marker) = self.peek() {
-                    match marker {
+                match self.peek() {
+                    Some(
                         Marker::FixExt1
                         | Marker::FixExt2
                         | Marker::FixExt4
@@ -188,84 +349,64 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
                         | Marker::FixExt16
                         | Marker::Ext8
                         | Marker::Ext16
-                        | Marker::Ext32 => {
-                            let (header_len, data_len) = crate::ext::read_ext_len(&self.slice[self.index..])?;
-                            self.index += header_len - 1; // move forward minus 1 byte for the ext type (header_len includes the type byte)
-                            self.state = State::Ext(data_len);
-                            visitor.visit_seq(SeqAccess::new(self, 2))
-                        }
-                        _ => Err(Error::InvalidType),
-                    }
-                } else {
-                    Err(Error::EndOfBuffer(Marker::Reserved))
+                        | Marker::Ext32,
+                    ) => self.deserialize_ext(visitor),
+                    Some(_) => Err(Error::InvalidType),
+                    None => Err(Error::EndOfBuffer(Marker::Reserved)),
                 }
             }
-            _ => self.deserialize_map(visitor),
+            // structs can be encoded as arrays of their values as well
+            _ => match (self.peek(), self.config.struct_repr()) {
+                (Some(Marker::FixArray(_) | Marker::Array16 | Marker::Array32), None | Some(StructRepr::Array)) => self.deserialize_seq(visitor),
+                (_, Some(StructRepr::Array)) => Err(Error::InvalidArrayType),
+                _ => self.deserialize_map(visitor),
+            },
         }
     }
 
     fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value> {
         print_debug::<V>("Deserializer::deserialize_", "enum", self);
-        visitor.visit_enum(UnitVariantAccess::new(self))
+        let in_map = match self.peek() {
+            Some(Marker::FixMap(_) | Marker::Map16 | Marker::Map32) => {
+                let limits = self.config.limits();
+                let (len, header_len) = limits.read_map_len(&self.slice[self.index..])?;
+                // externally tagged as `{ variant: payload }`
+                if len != 1 {
+                    return Err(Error::InvalidMapType);
+                }
+                self.index += header_len;
+                self.budget.elements(&limits, len)?;
+                true
+            }
+            _ => false,
+        };
+        // the payload of a bare variant nests as well, recursive enums could exhaust the stack otherwise
+        self.enter()?;
+        let value = visitor.visit_enum(EnumAccess::new(self, in_map));
+        self.depth -= 1;
+        value
     }
 
     fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
         print_debug::<V>("Deserializer::deserialize_", "identifier", self);
-        let marker = self.peek().ok_or(Error::EndOfBuffer(Marker::Reserved))?;
-        #[allow(clippy::single_match)]
-        match marker {
-            Marker::FixMap(_) => {
-                let (_len, header_len) = crate::decode::read_map_len(&self.slice[self.index..])?;
-                self.index += header_len;
-            }
-            _ => {}
+        // enum variants can be identified by their index as well
+        match self.peek() {
+            Some(Marker::FixPos(_) | Marker::U8 | Marker::U16 | Marker::U32 | Marker::U64) => self.deserialize_u64(visitor),
+            _ => self.deserialize_str(visitor),
         }
-        self.deserialize_str(visitor)
     }
 
-    /// Unsupported. Can’t parse a value without knowing its expected type.
+    /// Visits the value with the type its marker tells. Ext values are visited as a sequence of their type and data,
+    /// which is what [`Ext`](crate::Ext) and [`Timestamp`](crate::timestamp::Timestamp) expect.
     fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        let marker = self.peek().ok_or(Error::EndOfBuffer(Marker::Reserved))?;
-        match marker {
-            Marker::FixPos(_) => self.deserialize_u8(visitor),
-            Marker::FixMap(_) => self.deserialize_map(visitor),
-            Marker::Map16 => self.deserialize_map(visitor),
-            Marker::Map32 => self.deserialize_map(visitor),
-            Marker::FixArray(_) => self.deserialize_seq(visitor),
-            Marker::Array16 => self.deserialize_seq(visitor),
-            Marker::Array32 => self.deserialize_seq(visitor),
-            Marker::Str8 => self.deserialize_str(visitor),
-            Marker::Str16 => self.deserialize_str(visitor),
-            Marker::Str32 => self.deserialize_str(visitor),
-            Marker::Bin8 => self.deserialize_bytes(visitor),
-            Marker::Bin16 => self.deserialize_bytes(visitor),
-            Marker::Bin32 => self.deserialize_bytes(visitor),
-            Marker::FixStr(_) => self.deserialize_str(visitor),
-            Marker::F32 => self.deserialize_f32(visitor),
-            Marker::F64 => self.deserialize_f64(visitor),
-            Marker::I16 => self.deserialize_i16(visitor),
-            Marker::I32 => self.deserialize_i32(visitor),
-            Marker::I64 => self.deserialize_i64(visitor),
-            Marker::I8 => self.deserialize_i8(visitor),
-            Marker::U16 => self.deserialize_u16(visitor),
-            Marker::U32 => self.deserialize_u32(visitor),
-            Marker::U64 => self.deserialize_u64(visitor),
-            Marker::U8 => self.deserialize_u8(visitor),
-            Marker::True => self.deserialize_bool(visitor),
-            Marker::False => self.deserialize_bool(visitor),
-            _ => {
-                print_debug::<V>("Deserializer::deserialize_", "any", self);
-                let (_, n) = super::skip_any(&self.slice[self.index..])?;
-                self.index += n;
-                visitor.visit_unit()
-            }
-        }
+        self.visit_any(visitor, true)
     }
 
     /// Used to throw out fields that we don’t want to keep in our structs.
     fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
         print_debug::<V>("Deserializer::deserialize_", "ignored_any", self);
-        self.deserialize_any(visitor)
+        // nothing is kept, so nothing is copied
+        self.visit_any(visitor, false)
     }
 
     /// Unsupported. Use a more specific deserialize_* method
@@ -300,7 +441,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
     fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
         print_debug::<V>("Deserializer::deserialize_", "string", self);
-        self.deserialize_str(visitor)
+        self.visit_str(visitor, true)
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
@@ -325,6 +466,28 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_u64(value)
     }
 
+    fn deserialize_i128<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        print_debug::<V>("Deserializer::deserialize_", "i128", self);
+        let (value, len) = super::read_i128(&self.slice[self.index..])?;
+        self.index += len;
+        print_debug_value::<i128, i128>("Deserializer::deserialize_i128", self, &value);
+        visitor.visit_i128(value)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        print_debug::<V>("Deserializer::deserialize_", "u128", self);
+        let (value, len) = super::read_u128(&self.slice[self.index..])?;
+        self.index += len;
+        print_debug_value::<u128, u128>("Deserializer::deserialize_u128", self, &value);
+        visitor.visit_u128(value)
+    }
+
     fn deserialize_f64<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
@@ -364,7 +527,8 @@ impl de::Error for Error {
             use core::fmt::Write;
 
             let mut string = heapless::String::new();
-            write!(string, "{:.512}", msg).unwrap();
+            // messages of serde, e.g. from untagged enums trying each variant, often don't fit and are cut off
+            let _ = write!(crate::encode::serde::Truncating(&mut string), "{}", msg);
             Error::CustomErrorWithMessage(string)
         }
         #[cfg(all(feature = "std", feature = "custom-error-messages"))]
@@ -402,6 +566,19 @@ impl fmt::Display for Error {
                 Error::InvalidMapType => "Invalid map marker.",
                 Error::InvalidNewTypeLength => "Invalid array length for newtype.",
                 Error::InvalidUtf8(_) => "Invalid Utf8.",
+                Error::TrailingBytes => "Trailing bytes after the value.",
+                Error::TrailingElements => "Trailing elements in an array or map.",
+                Error::DepthLimitExceeded => "Nesting depth limit exceeded.",
+                Error::LimitExceeded(Limit::Elements) => "Too many elements.",
+                Error::LimitExceeded(Limit::StrLen) => "String too long.",
+                Error::LimitExceeded(Limit::BinLen) => "Binary too long.",
+                Error::LimitExceeded(Limit::MapEntries) => "Too many map entries.",
+                Error::LimitExceeded(Limit::AllocBytes) => "Too many bytes to allocate.",
+                #[cfg(feature = "std")]
+                Error::Io(e) => {
+                    s = format!("I/O error: {}", e);
+                    s.as_str()
+                }
             }
         )
     }
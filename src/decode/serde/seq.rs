@@ -1,19 +1,27 @@
 use serde::de;
 
-use super::{print_debug, Deserializer, Error};
+use super::{print_debug, Deserializer, DeserializerConfig, Error};
 
-pub(crate) struct SeqAccess<'a, 'b> {
-    de: &'a mut Deserializer<'b>,
+pub(crate) struct SeqAccess<'a, 'b, C> {
+    de: &'a mut Deserializer<'b, C>,
     count: usize,
 }
 
-impl<'a, 'b> SeqAccess<'a, 'b> {
-    pub fn new(de: &'a mut Deserializer<'b>, count: usize) -> Self {
+impl<'a, 'b, C> SeqAccess<'a, 'b, C> {
+    pub const fn new(de: &'a mut Deserializer<'b, C>, count: usize) -> Self {
         SeqAccess { de, count }
     }
+
+    /// Fails if the visitor didn't deserialize all elements
+    pub(crate) const fn end(&self) -> Result<(), Error> {
+        if self.count > 0 {
+            return Err(Error::TrailingElements);
+        }
+        Ok(())
+    }
 }
 
-impl<'a, 'de> de::SeqAccess<'de> for SeqAccess<'a, 'de> {
+impl<'a, 'de, C: DeserializerConfig> de::SeqAccess<'de> for SeqAccess<'a, 'de, C> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
@@ -0,0 +1,84 @@
+use core::marker::PhantomData;
+
+use super::{from_slice_strict_with_config, layout, DefaultConfig, DeserializerConfig, Error};
+
+/// Iterator over values stored back to back in a buffer
+///
+/// Every item is the next value deserialized as `T`. The iteration ends after the last complete value, or after the
+/// first error. If the buffer ends inside a value, that error is [`Error::EndOfBuffer`] and
+/// [`remaining`](Self::remaining) holds the start of the value, e.g. to retry once more data was read. Other errors
+/// mean the data is corrupt or doesn't match `T`, e.g. [`Error::TrailingBytes`] if `T` doesn't take the whole value.
+pub struct StreamDeserializer<'a, T, C = DefaultConfig> {
+    buf: &'a [u8],
+    pos: usize,
+    failed: bool,
+    config: C,
+    value: PhantomData<T>,
+}
+
+impl<'a, T> StreamDeserializer<'a, T>
+where
+    T: ::serde::de::Deserialize<'a>,
+{
+    pub const fn new(buf: &'a [u8]) -> Self {
+        Self::with_config(buf, DefaultConfig)
+    }
+}
+
+impl<'a, T, C> StreamDeserializer<'a, T, C>
+where
+    T: ::serde::de::Deserialize<'a>,
+    C: DeserializerConfig,
+{
+    /// Like [`StreamDeserializer::new`], with the given deserializer options
+    pub const fn with_config(buf: &'a [u8], config: C) -> Self {
+        StreamDeserializer {
+            buf,
+            pos: 0,
+            failed: false,
+            config,
+            value: PhantomData,
+        }
+    }
+
+    /// Number of bytes taken by the values returned so far
+    pub const fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// The bytes after the values returned so far
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.buf[self.pos..]
+    }
+}
+
+impl<'a, T, C> Iterator for StreamDeserializer<'a, T, C>
+where
+    T: ::serde::de::Deserialize<'a>,
+    C: DeserializerConfig,
+{
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.pos == self.buf.len() {
+            return None;
+        }
+        let buf = &self.buf[self.pos..];
+        // whether the value is complete only depends on its markers and lengths, so any error of the deserializer
+        // after that means the data is corrupt. `T` has to take all of it, the next value starts right after.
+        let result = match layout::value_len(buf) {
+            Ok(len) => from_slice_strict_with_config(&buf[..len], self.config).map(|value| (value, len)),
+            Err(truncated) => Err(Error::EndOfBuffer(truncated.marker)),
+        };
+        match result {
+            Ok((value, len)) => {
+                self.pos += len;
+                Some(Ok(value))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
@@ -1,5 +1,10 @@
 #[cfg(feature = "serde")]
 pub mod serde;
+mod write;
+
+#[cfg(feature = "std")]
+pub use write::IoWriter;
+pub use write::{ChunkWriter, SizeCounter, SliceWriter, Write, MAX_ITEM_LEN};
 
 use crate::marker::Marker;
 
@@ -23,6 +28,27 @@ pub enum Error {
     OutOfBounds,
     /// Happens if the data type can not be serialized. For example if a sequence is not sized.
     InvalidType,
+    /// A map contained the same key twice, which is not allowed in canonical mode.
+    DuplicateKey,
+    /// The output can't be changed once written, which skipping `None` fields, canonical mode and sequences of
+    /// unknown length need.
+    NotSeekable,
+    /// A string, binary, array or map is longer than the largest length the enabled features can encode.
+    LengthExceedsFormat {
+        /// The largest length that can be encoded
+        max: usize,
+    },
+    /// A [`Serialize`](::serde::Serialize) implementation reported an error.
+    CustomError,
+    /// A [`Serialize`](::serde::Serialize) implementation reported an error, with its message truncated to 64 bytes.
+    #[cfg(all(not(feature = "std"), feature = "custom-error-messages"))]
+    CustomErrorWithMessage(heapless::String<64>),
+    /// A [`Serialize`](::serde::Serialize) implementation reported an error, with its message.
+    #[cfg(all(feature = "std", feature = "custom-error-messages"))]
+    CustomErrorWithMessage(String),
+    /// The underlying [`std::io::Write`] failed.
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
 }
 
 impl ::core::fmt::Display for Error {
@@ -31,6 +57,14 @@ impl ::core::fmt::Display for Error {
             Error::OutOfBounds => f.write_str("Out of bounds"),
             Error::InvalidType => f.write_str("Invalid type"),
             Error::EndOfBuffer => f.write_str("End of buffer"),
+            Error::DuplicateKey => f.write_str("Duplicate map key"),
+            Error::NotSeekable => f.write_str("Output can't be changed once written"),
+            Error::LengthExceedsFormat { max } => write!(f, "Length exceeds the maximum of {}", max),
+            Error::CustomError => f.write_str("Custom serialization error"),
+            #[cfg(feature = "custom-error-messages")]
+            Error::CustomErrorWithMessage(msg) => f.write_str(msg.as_str()),
+            #[cfg(feature = "std")]
+            Error::Io(e) => write!(f, "I/O error: {}", e),
         }
     }
 }
@@ -148,6 +182,10 @@ pub fn serialize_i64(value: i64, buf: &mut [u8]) -> Result<usize, Error> {
     if let Some(value) = u64::from_i64(value) {
         return serialize_u64(value, buf);
     }
+    #[cfg(not(feature = "u64"))]
+    if let Some(value) = u32::from_i64(value) {
+        return serialize_u32(value, buf);
+    }
     if let Some(value) = i32::from_i64(value) {
         serialize_i32(value, buf)
     } else {
@@ -159,6 +197,45 @@ pub fn serialize_i64(value: i64, buf: &mut [u8]) -> Result<usize, Error> {
         Ok(9)
     }
 }
+/// Writes a `u128` with the smallest integer marker if it fits, otherwise as `bin 8` of its 16 big-endian bytes
+/// (the representation rmp-serde uses for all 128-bit integers)
+pub fn serialize_u128(value: u128, buf: &mut [u8]) -> Result<usize, Error> {
+    #[cfg(feature = "u64")]
+    if let Ok(value) = u64::try_from(value) {
+        return serialize_u64(value, buf);
+    }
+    #[cfg(not(feature = "u64"))]
+    if let Ok(value) = u32::try_from(value) {
+        return serialize_u32(value, buf);
+    }
+    serialize_int128_bytes(value.to_be_bytes(), buf)
+}
+/// Writes an `i128` with the smallest integer marker if it fits, otherwise as `bin 8` of its 16 big-endian bytes
+/// in two's complement (the representation rmp-serde uses for all 128-bit integers)
+pub fn serialize_i128(value: i128, buf: &mut [u8]) -> Result<usize, Error> {
+    #[cfg(feature = "u64")]
+    if let Ok(value) = u64::try_from(value) {
+        return serialize_u64(value, buf);
+    }
+    #[cfg(feature = "i64")]
+    if let Ok(value) = i64::try_from(value) {
+        return serialize_i64(value, buf);
+    }
+    #[cfg(not(feature = "i64"))]
+    if let Ok(value) = i32::try_from(value) {
+        return serialize_i32(value, buf);
+    }
+    serialize_int128_bytes(value.to_be_bytes(), buf)
+}
+fn serialize_int128_bytes(bytes: [u8; 16], buf: &mut [u8]) -> Result<usize, Error> {
+    let header_len = serialize_bin_start(bytes.len(), buf)?;
+    let len = header_len + bytes.len();
+    if buf.len() < len {
+        return Err(Error::EndOfBuffer);
+    }
+    buf[header_len..len].copy_from_slice(&bytes);
+    Ok(len)
+}
 pub fn serialize_f32(value: f32, buf: &mut [u8]) -> Result<usize, Error> {
     if buf.len() < 5 {
         return Err(Error::EndOfBuffer);
@@ -226,6 +303,18 @@ impl SerializeIntoSlice for i64 {
         serialize_i64(*self, buf)
     }
 }
+impl SerializeIntoSlice for u128 {
+    #[inline(always)]
+    fn write_into_slice(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        serialize_u128(*self, buf)
+    }
+}
+impl SerializeIntoSlice for i128 {
+    #[inline(always)]
+    fn write_into_slice(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        serialize_i128(*self, buf)
+    }
+}
 
 impl SerializeIntoSlice for f32 {
     #[inline(always)]
@@ -350,7 +439,8 @@ impl<'de> ::serde::de::Visitor<'de> for BinaryVisitor {
         extern crate alloc;
         #[cfg(not(feature = "std"))]
         use alloc::vec::Vec;
-        let mut data = seq.size_hint().map_or_else(Vec::new, Vec::with_capacity);
+        // the hint comes from the input, don't let it reserve more than a small start
+        let mut data = seq.size_hint().map_or_else(Vec::new, |len| Vec::with_capacity(len.min(4096)));
         while let Some(e) = seq.next_element::<u8>()? {
             data.push(e);
         }
@@ -364,46 +454,61 @@ impl<'a> ::serde::Deserialize<'a> for Binary<'a> {
     }
 }
 
-/// # Panics
-///
-/// Will panic under the following conditions:
-///  - feature 'bin32' active: `n >= 2^32`
-///  - feature 'bin16' active: `n >= 2^16`
-///  - else: `n >= 2^8`
 impl<'a> SerializeIntoSlice for Binary<'a> {
     fn write_into_slice(&self, buf: &mut [u8]) -> Result<usize, Error> {
         let n = self.len();
-        if let Ok(n8) = u8::try_from(n) {
-            if buf.len() < 2 + n {
-                return Err(Error::EndOfBuffer);
-            }
-            buf[0] = Marker::Bin8.to_u8();
-            buf[1] = n8;
-            buf[2..(2 + n)].clone_from_slice(self);
-            return Ok(2 + n);
-        }
-        #[cfg(feature = "bin16")]
-        if let Ok(n16) = u16::try_from(n) {
-            if buf.len() < 3 + n {
-                return Err(Error::EndOfBuffer);
-            }
-            buf[0] = Marker::Bin16.to_u8();
-            BigEndian::write_u16(&mut buf[1..], n16);
-            buf[3..(3 + n)].clone_from_slice(self);
-            return Ok(3 + n);
-        }
-        #[cfg(feature = "bin32")]
-        if let Ok(n32) = u32::try_from(n) {
-            if buf.len() < 5 + n {
-                return Err(Error::EndOfBuffer);
-            }
-            buf[0] = Marker::Bin32.to_u8();
-            BigEndian::write_u32(&mut buf[1..], n32);
-            buf[5..(5 + n)].clone_from_slice(self);
-            return Ok(5 + n);
+        let header_len = serialize_bin_start(n, buf)?;
+        if buf.len() < header_len + n {
+            return Err(Error::EndOfBuffer);
+        }
+        buf[header_len..(header_len + n)].clone_from_slice(self);
+        Ok(header_len + n)
+    }
+}
+
+/// Largest binary length [`serialize_bin_start`] can encode with the enabled features
+#[cfg(feature = "bin32")]
+pub const MAX_BIN_LEN: usize = u32::MAX as usize;
+/// Largest binary length [`serialize_bin_start`] can encode with the enabled features
+#[cfg(all(feature = "bin16", not(feature = "bin32")))]
+pub const MAX_BIN_LEN: usize = u16::MAX as usize;
+/// Largest binary length [`serialize_bin_start`] can encode with the enabled features
+#[cfg(not(any(feature = "bin16", feature = "bin32")))]
+pub const MAX_BIN_LEN: usize = u8::MAX as usize;
+
+/// Writes the marker and length of a binary of `n` bytes. The data has to follow.
+///
+/// # Errors
+///
+/// Fails with [`Error::LengthExceedsFormat`] if `n` is larger than [`MAX_BIN_LEN`].
+pub fn serialize_bin_start(n: usize, buf: &mut [u8]) -> Result<usize, Error> {
+    if let Ok(n8) = u8::try_from(n) {
+        if buf.len() < 2 {
+            return Err(Error::EndOfBuffer);
+        }
+        buf[0] = Marker::Bin8.to_u8();
+        buf[1] = n8;
+        return Ok(2);
+    }
+    #[cfg(feature = "bin16")]
+    if let Ok(n16) = u16::try_from(n) {
+        if buf.len() < 3 {
+            return Err(Error::EndOfBuffer);
         }
-        unimplemented!()
+        buf[0] = Marker::Bin16.to_u8();
+        BigEndian::write_u16(&mut buf[1..], n16);
+        return Ok(3);
     }
+    #[cfg(feature = "bin32")]
+    if let Ok(n32) = u32::try_from(n) {
+        if buf.len() < 5 {
+            return Err(Error::EndOfBuffer);
+        }
+        buf[0] = Marker::Bin32.to_u8();
+        BigEndian::write_u32(&mut buf[1..], n32);
+        return Ok(5);
+    }
+    Err(Error::LengthExceedsFormat { max: MAX_BIN_LEN })
 }
 
 impl<K, V> SerializeIntoSlice for &(K, V)
@@ -418,62 +523,72 @@ where
     }
 }
 
-/// # Panics
-///
-/// Will panic under the following conditions:
-///  - feature 'str32' active: `n >= 2^32`
-///  - feature 'str16' active: `n >= 2^16`
-///  - else: `n >= 2^8`
 impl SerializeIntoSlice for &str {
-    #[allow(clippy::cast_possible_truncation)]
     fn write_into_slice(&self, buf: &mut [u8]) -> Result<usize, Error> {
         let n = self.len();
-        match n {
-            // FIXSTR_SIZE
-            0..=0x1f => {
-                let header_len = 1;
-                if buf.len() < header_len + n {
-                    return Err(Error::EndOfBuffer);
-                }
-                buf[0] = Marker::FixStr(n as u8).to_u8();
-                buf[header_len..(header_len + n)].clone_from_slice(self.as_bytes());
-                Ok(header_len + n)
+        let header_len = serialize_str_start(n, buf)?;
+        if buf.len() < header_len + n {
+            return Err(Error::EndOfBuffer);
+        }
+        buf[header_len..(header_len + n)].clone_from_slice(self.as_bytes());
+        Ok(header_len + n)
+    }
+}
+
+/// Largest string length in bytes [`serialize_str_start`] can encode with the enabled features
+#[cfg(feature = "str32")]
+pub const MAX_STR_LEN: usize = u32::MAX as usize;
+/// Largest string length in bytes [`serialize_str_start`] can encode with the enabled features
+#[cfg(all(feature = "str16", not(feature = "str32")))]
+pub const MAX_STR_LEN: usize = u16::MAX as usize;
+/// Largest string length in bytes [`serialize_str_start`] can encode with the enabled features
+#[cfg(not(any(feature = "str16", feature = "str32")))]
+pub const MAX_STR_LEN: usize = u8::MAX as usize;
+
+/// Writes the marker and length of a string of `n` bytes. The UTF-8 data has to follow.
+///
+/// # Errors
+///
+/// Fails with [`Error::LengthExceedsFormat`] if `n` is larger than [`MAX_STR_LEN`].
+#[allow(clippy::cast_possible_truncation)]
+pub fn serialize_str_start(n: usize, buf: &mut [u8]) -> Result<usize, Error> {
+    match n {
+        // FIXSTR_SIZE
+        0..=0x1f => {
+            if buf.is_empty() {
+                return Err(Error::EndOfBuffer);
             }
-            0x20..=0xff => {
-                let header_len = 2;
-                if buf.len() < header_len + n {
+            buf[0] = Marker::FixStr(n as u8).to_u8();
+            Ok(1)
+        }
+        0x20..=0xff => {
+            if buf.len() < 2 {
+                return Err(Error::EndOfBuffer);
+            }
+            buf[0] = Marker::Str8.to_u8();
+            buf[1] = n as u8;
+            Ok(2)
+        }
+        _ => {
+            #[cfg(feature = "str16")]
+            if let Ok(n16) = u16::try_from(n) {
+                if buf.len() < 3 {
                     return Err(Error::EndOfBuffer);
                 }
-                buf[0] = Marker::Str8.to_u8();
-                buf[1] = n as u8;
-                buf[header_len..(header_len + n)].clone_from_slice(self.as_bytes());
-                Ok(header_len + n)
+                buf[0] = Marker::Str16.to_u8();
+                BigEndian::write_u16(&mut buf[1..], n16);
+                return Ok(3);
             }
-            _ => {
-                #[cfg(feature = "str16")]
-                if let Ok(n16) = u16::try_from(n) {
-                    let header_len = 3;
-                    if buf.len() < header_len + n {
-                        return Err(Error::EndOfBuffer);
-                    }
-                    buf[0] = Marker::Str16.to_u8();
-                    BigEndian::write_u16(&mut buf[1..], n16);
-                    buf[header_len..(header_len + n)].clone_from_slice(self.as_bytes());
-                    return Ok(header_len + n);
-                }
-                #[cfg(feature = "str32")]
-                if let Ok(n32) = u32::try_from(n) {
-                    let header_len = 5;
-                    if buf.len() < header_len + n {
-                        return Err(Error::EndOfBuffer);
-                    }
-                    buf[0] = Marker::Str32.to_u8();
-                    BigEndian::write_u32(&mut buf[1..], n32);
-                    buf[header_len..(header_len + n)].clone_from_slice(self.as_bytes());
-                    return Ok(header_len + n);
+            #[cfg(feature = "str32")]
+            if let Ok(n32) = u32::try_from(n) {
+                if buf.len() < 5 {
+                    return Err(Error::EndOfBuffer);
                 }
-                unimplemented!()
+                buf[0] = Marker::Str32.to_u8();
+                BigEndian::write_u32(&mut buf[1..], n32);
+                return Ok(5);
             }
+            Err(Error::LengthExceedsFormat { max: MAX_STR_LEN })
         }
     }
 }
@@ -529,12 +644,31 @@ pub fn serialize_sequence<T: SerializeIntoSlice>(seq: &[T], typ: SequenceType, b
     Ok(index)
 }
 
-/// # Panics
+/// Largest element count [`serialize_array_start`] can encode with the enabled features
+#[cfg(feature = "array32")]
+pub const MAX_ARRAY_LEN: usize = u32::MAX as usize;
+/// Largest element count [`serialize_array_start`] can encode with the enabled features
+#[cfg(all(feature = "array16", not(feature = "array32")))]
+pub const MAX_ARRAY_LEN: usize = u16::MAX as usize;
+/// Largest element count [`serialize_array_start`] can encode with the enabled features
+#[cfg(not(feature = "array16"))]
+pub const MAX_ARRAY_LEN: usize = crate::marker::FIXARRAY_SIZE as usize;
+
+/// Largest entry count [`serialize_map_start`] can encode with the enabled features
+#[cfg(feature = "map32")]
+pub const MAX_MAP_LEN: usize = u32::MAX as usize;
+/// Largest entry count [`serialize_map_start`] can encode with the enabled features
+#[cfg(all(feature = "map16", not(feature = "map32")))]
+pub const MAX_MAP_LEN: usize = u16::MAX as usize;
+/// Largest entry count [`serialize_map_start`] can encode with the enabled features
+#[cfg(not(any(feature = "map16", feature = "map32")))]
+pub const MAX_MAP_LEN: usize = crate::marker::FIXMAP_SIZE as usize;
+
+/// Writes the marker and length of an array of `n` elements. The elements have to follow.
 ///
-/// Will panic under the following conditions:
-///  - feature 'array32' active: `n >= 2^32`
-///  - feature 'array16' active: `n >= 2^16`
-///  - else: `n >= 16`
+/// # Errors
+///
+/// Fails with [`Error::LengthExceedsFormat`] if `n` is larger than [`MAX_ARRAY_LEN`].
 #[allow(clippy::cast_possible_truncation)]
 pub fn serialize_array_start(n: usize, buf: &mut [u8]) -> Result<usize, Error> {
     if n <= crate::marker::FIXARRAY_SIZE as usize {
@@ -546,26 +680,31 @@ pub fn serialize_array_start(n: usize, buf: &mut [u8]) -> Result<usize, Error> {
     } else {
         #[cfg(feature = "array16")]
         if let Ok(n) = u16::try_from(n) {
+            if buf.len() < 3 {
+                return Err(Error::EndOfBuffer);
+            }
             buf[0] = Marker::Array16.to_u8();
             BigEndian::write_u16(&mut buf[1..], n);
             return Ok(3);
         }
         #[cfg(feature = "array32")]
         if let Ok(n) = u32::try_from(n) {
+            if buf.len() < 5 {
+                return Err(Error::EndOfBuffer);
+            }
             buf[0] = Marker::Array32.to_u8();
             BigEndian::write_u32(&mut buf[1..], n);
             return Ok(5);
         }
-        unimplemented!()
+        Err(Error::LengthExceedsFormat { max: MAX_ARRAY_LEN })
     }
 }
 
-/// # Panics
+/// Writes the marker and length of a map of `n` entries. The keys and values have to follow.
 ///
-/// Will panic under the following conditions:
-///  - feature 'map32' active: `n >= 2^32`
-///  - feature 'map16' active: `n >= 2^16`
-///  - else: `n >= 16`
+/// # Errors
+///
+/// Fails with [`Error::LengthExceedsFormat`] if `n` is larger than [`MAX_MAP_LEN`].
 #[allow(clippy::cast_possible_truncation)]
 pub fn serialize_map_start(n: usize, buf: &mut [u8]) -> Result<usize, Error> {
     if n <= crate::marker::FIXMAP_SIZE as usize {
@@ -577,17 +716,23 @@ pub fn serialize_map_start(n: usize, buf: &mut [u8]) -> Result<usize, Error> {
     } else {
         #[cfg(feature = "map16")]
         if let Ok(n) = u16::try_from(n) {
+            if buf.len() < 3 {
+                return Err(Error::EndOfBuffer);
+            }
             buf[0] = Marker::Map16.to_u8();
             BigEndian::write_u16(&mut buf[1..], n);
             return Ok(3);
         }
         #[cfg(feature = "map32")]
         if let Ok(n) = u32::try_from(n) {
+            if buf.len() < 5 {
+                return Err(Error::EndOfBuffer);
+            }
             buf[0] = Marker::Map32.to_u8();
             BigEndian::write_u32(&mut buf[1..], n);
             return Ok(5);
         }
-        unimplemented!()
+        Err(Error::LengthExceedsFormat { max: MAX_MAP_LEN })
     }
 }
 pub fn serialize_map_kay_value<K: SerializeIntoSlice, V: SerializeIntoSlice>(key: &K, value: &V, buf: &mut [u8]) -> Result<usize, Error> {
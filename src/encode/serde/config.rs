@@ -0,0 +1,156 @@
+/// How structs are laid out on the wire
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StructRepr {
+    /// A map of field names to values. Self describing and tolerant to reordered or added fields.
+    Map,
+    /// An array of the values in declaration order, without field names. This is what rmp-serde writes by default.
+    Array,
+}
+
+/// How enum variants are identified on the wire
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnumRepr {
+    /// The name of the variant as a string
+    Name,
+    /// The index of the variant as an integer. Smaller, and unaffected by renaming variants, but reordering or
+    /// inserting variants changes the encoding.
+    Index,
+}
+
+/// Options of the serde serializer
+///
+/// Implementations with constant answers (like [`DefaultConfig`]) are resolved at compile time, so the options
+/// that are not used don't cost any code size. [`SerializerOptions`] allows choosing them at runtime.
+pub trait SerializerConfig: Copy {
+    /// Representation of structs and the fields of struct variants, [`StructRepr::Map`] by default
+    #[inline(always)]
+    fn struct_repr(&self) -> StructRepr {
+        StructRepr::Map
+    }
+    /// Identification of enum variants, [`EnumRepr::Name`] by default
+    #[inline(always)]
+    fn enum_repr(&self) -> EnumRepr {
+        EnumRepr::Name
+    }
+    /// Leave out struct fields that are `None` instead of writing `nil`, `false` by default
+    ///
+    /// Only applies to structs encoded as maps. The length of the map is patched once the struct is complete, which
+    /// requires a [`Write`](crate::encode::Write) sink that supports patching.
+    #[inline(always)]
+    fn skip_none_fields(&self) -> bool {
+        false
+    }
+    /// Write floats in the smallest lossless representation, `false` by default
+    ///
+    /// Integral values that fit into an `i32` or `u32` are written as integers, other values that an `f32` holds
+    /// exactly as `f32`. The deserializer accepts all of them for `f32` and `f64` fields, but other decoders might not.
+    #[inline(always)]
+    fn compact_floats(&self) -> bool {
+        false
+    }
+    /// Produce the canonical encoding checked by [`is_canonical`](crate::decode::is_canonical), `false` by default
+    ///
+    /// Map entries are sorted by their encoded keys, duplicate keys fail with
+    /// [`Error::DuplicateKey`](crate::encode::Error::DuplicateKey) and NaN is written without payload. The entries
+    /// are reordered in the output, which requires a [`Write`](crate::encode::Write) sink that keeps it in memory.
+    /// [`SizeCounter`](crate::encode::SizeCounter) is fine as well since sorting doesn't change the size, but it
+    /// can't detect duplicate keys.
+    #[inline(always)]
+    fn canonical(&self) -> bool {
+        false
+    }
+}
+
+/// Encodes structs as maps
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultConfig;
+
+impl SerializerConfig for DefaultConfig {}
+
+/// Encodes structs as arrays of their values, leaving out the field names
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CompactConfig;
+
+impl SerializerConfig for CompactConfig {
+    #[inline(always)]
+    fn struct_repr(&self) -> StructRepr {
+        StructRepr::Array
+    }
+}
+
+/// Serializer options chosen at runtime, e.g. `SerializerOptions::new().skip_none_fields(true)`
+#[derive(Clone, Copy, Debug)]
+pub struct SerializerOptions {
+    struct_repr: StructRepr,
+    enum_repr: EnumRepr,
+    skip_none_fields: bool,
+    compact_floats: bool,
+    canonical: bool,
+}
+
+impl SerializerOptions {
+    /// Options with the same behaviour as [`DefaultConfig`]
+    pub const fn new() -> Self {
+        SerializerOptions {
+            struct_repr: StructRepr::Map,
+            enum_repr: EnumRepr::Name,
+            skip_none_fields: false,
+            compact_floats: false,
+            canonical: false,
+        }
+    }
+    /// See [`SerializerConfig::struct_repr`]
+    pub const fn struct_repr(mut self, repr: StructRepr) -> Self {
+        self.struct_repr = repr;
+        self
+    }
+    /// See [`SerializerConfig::enum_repr`]
+    pub const fn enum_repr(mut self, repr: EnumRepr) -> Self {
+        self.enum_repr = repr;
+        self
+    }
+    /// See [`SerializerConfig::skip_none_fields`]
+    pub const fn skip_none_fields(mut self, skip: bool) -> Self {
+        self.skip_none_fields = skip;
+        self
+    }
+    /// See [`SerializerConfig::compact_floats`]
+    pub const fn compact_floats(mut self, compact: bool) -> Self {
+        self.compact_floats = compact;
+        self
+    }
+    /// See [`SerializerConfig::canonical`]
+    pub const fn canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+}
+
+impl Default for SerializerOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SerializerConfig for SerializerOptions {
+    #[inline]
+    fn struct_repr(&self) -> StructRepr {
+        self.struct_repr
+    }
+    #[inline]
+    fn enum_repr(&self) -> EnumRepr {
+        self.enum_repr
+    }
+    #[inline]
+    fn skip_none_fields(&self) -> bool {
+        self.skip_none_fields
+    }
+    #[inline]
+    fn compact_floats(&self) -> bool {
+        self.compact_floats
+    }
+    #[inline]
+    fn canonical(&self) -> bool {
+        self.canonical
+    }
+}
@@ -1,30 +1,49 @@
 use serde::ser;
 
-use super::{Error, Serializer};
+use super::{Error, Serializer, SerializerConfig, UnsizedHeader};
+use crate::encode::Write;
 
-pub struct SerializeMap<'a, 'b> {
-    ser: &'a mut Serializer<'b>,
+pub struct SerializeMap<'a, W, C> {
+    ser: &'a mut Serializer<W, C>,
+    header: Option<UnsizedHeader>,
+    /// Position of the first entry
+    entries: usize,
+    /// Position of the entry being serialized
+    entry: usize,
 }
 
-impl<'a, 'b> SerializeMap<'a, 'b> {
-    pub(crate) fn new(ser: &'a mut Serializer<'b>) -> Self {
-        SerializeMap { ser }
+impl<'a, W: Write, C> SerializeMap<'a, W, C> {
+    pub(crate) fn new(ser: &'a mut Serializer<W, C>, header: Option<UnsizedHeader>) -> Self {
+        let entries = ser.writer.position();
+        SerializeMap {
+            ser,
+            header,
+            entries,
+            entry: entries,
+        }
     }
 }
 
-impl<'a, 'b> ser::SerializeMap for SerializeMap<'a, 'b> {
+impl<'a, W: Write, C: SerializerConfig> ser::SerializeMap for SerializeMap<'a, W, C> {
     type Ok = ();
     type Error = Error;
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        match self.header {
+            Some(header) => self.ser.end_unsized(crate::encode::serialize_map_start, &header),
+            None => Ok(()),
+        }
     }
 
     fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<Self::Ok, Self::Error>
     where
         T: ser::Serialize,
     {
+        self.entry = self.ser.writer.position();
         key.serialize(&mut *self.ser)?;
+        if let Some(header) = &mut self.header {
+            header.count += 1;
+        }
         Ok(())
     }
 
@@ -33,6 +52,9 @@ impl<'a, 'b> ser::SerializeMap for SerializeMap<'a, 'b> {
         T: ser::Serialize,
     {
         value.serialize(&mut *self.ser)?;
+        if self.ser.config.canonical() {
+            self.ser.sort_entry(self.entries, self.entry)?;
+        }
         Ok(())
     }
 }
@@ -1,16 +1,25 @@
+use core::cmp::Ordering;
 use serde::ser::{self};
 
 // use heapless::{consts::*, String, Vec};
 
 use self::{map::SerializeMap, seq::SerializeSeq, struct_::SerializeStruct};
 
+mod config;
 mod map;
 mod seq;
 mod struct_;
 
-use super::Error;
+pub use config::{CompactConfig, DefaultConfig, EnumRepr, SerializerConfig, SerializerOptions, StructRepr};
+
+use super::{ChunkWriter, Error, SizeCounter, SliceWriter, Write, MAX_ITEM_LEN};
 use crate::encode::SerializeIntoSlice;
 
+#[cfg(any(feature = "alloc", feature = "std"))]
+extern crate alloc;
+#[cfg(any(feature = "alloc", feature = "std"))]
+use alloc::vec::Vec;
+
 enum State {
     Normal,
     #[cfg(feature = "ext")]
@@ -19,44 +28,150 @@ enum State {
     Timestamp(Option<i64>, Option<u32>),
 }
 
-pub(crate) struct Serializer<'a> {
-    buf: &'a mut [u8],
-    pos: usize,
+pub(crate) struct Serializer<W, C = DefaultConfig> {
+    writer: W,
+    config: C,
     state: State,
 }
 
-impl<'a> Serializer<'a> {
-    fn new(buf: &'a mut [u8]) -> Self {
+impl<W: Write, C: SerializerConfig> Serializer<W, C> {
+    const fn new(writer: W, config: C) -> Self {
         Serializer {
-            buf,
-            pos: 0,
+            writer,
+            config,
             state: State::Normal,
         }
     }
     fn append<S: SerializeIntoSlice>(&mut self, value: S) -> Result<(), Error> {
-        self.pos += value.write_into_slice(&mut self.buf[self.pos..])?;
+        self.writer.write_with(|buf| value.write_into_slice(buf))
+    }
+    /// Writes the name or the index of an enum variant
+    fn serialize_variant(&mut self, variant_index: u32, variant: &'static str) -> Result<(), Error> {
+        match self.config.enum_repr() {
+            EnumRepr::Name => ser::Serializer::serialize_str(self, variant),
+            EnumRepr::Index => self.append(variant_index),
+        }
+    }
+    /// Writes `v` as an integer if that is lossless, returns whether it did
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn append_integral(&mut self, v: f64) -> Result<bool, Error> {
+        if !(f64::from(i32::MIN)..=f64::from(u32::MAX)).contains(&v) {
+            return Ok(false);
+        }
+        let i = v as i64;
+        // also rules out -0.0
+        if (i as f64).to_bits() != v.to_bits() {
+            return Ok(false);
+        }
+        if i >= 0 {
+            self.append(i as u32)?;
+        } else {
+            self.append(i as i32)?;
+        }
+        Ok(true)
+    }
+    /// Fails before writing anything if the sink can't go back to change its output
+    fn require_patch(&mut self) -> Result<(), Error> {
+        let pos = self.writer.position();
+        self.writer.patch(pos, &[])
+    }
+    /// Reserves room for the widest header `start` can write, to be filled in by [`Self::end_unsized`]
+    ///
+    /// Every element takes at least one byte after a header of at least one byte, so a fixed size output bounds the
+    /// length as well.
+    fn begin_unsized(&mut self, start: HeaderFn, max_len: usize) -> Result<UnsizedHeader, Error> {
+        self.require_patch()?;
+        let pos = self.writer.position();
+        let max_len = self.writer.remaining().map_or(max_len, |remaining| remaining.saturating_sub(1).min(max_len));
+        self.writer.write_with(|buf| start(max_len, buf))?;
+        Ok(UnsizedHeader {
+            pos,
+            len: self.writer.position() - pos,
+            count: 0,
+        })
+    }
+    /// Writes the header of a struct with `len` fields as configured
+    ///
+    /// Returns the reserved header if the number of fields is only known at the end.
+    fn struct_start(&mut self, len: usize) -> Result<Option<UnsizedHeader>, Error> {
+        match self.config.struct_repr() {
+            // the number of fields is only known at the end if `None` fields are left out
+            StructRepr::Map if self.config.skip_none_fields() => self.begin_unsized(super::serialize_map_start, len).map(Some),
+            StructRepr::Map => {
+                if self.config.canonical() {
+                    self.require_patch()?;
+                }
+                self.writer.write_with(|buf| super::serialize_map_start(len, buf))?;
+                Ok(None)
+            }
+            StructRepr::Array => {
+                self.writer.write_with(|buf| super::serialize_array_start(len, buf))?;
+                Ok(None)
+            }
+        }
+    }
+    /// Moves the map entry written last, starting at `entry`, in front of the first entry with a larger key
+    ///
+    /// The entries from `entries` up to `entry` are already sorted, so the map stays sorted by the encoded keys.
+    fn sort_entry(&mut self, entries: usize, entry: usize) -> Result<(), Error> {
+        // sinks that don't keep the output only count it, sorting doesn't change the size
+        let Some(buf) = self.writer.written_mut() else {
+            return Ok(());
+        };
+        let buf = &mut buf[entries..];
+        let new = entry - entries;
+        let key_len = value_len(&buf[new..])?;
+        let mut pos = 0;
+        while pos < new {
+            let len = value_len(&buf[pos..])?;
+            match buf[pos..pos + len].cmp(&buf[new..new + key_len]) {
+                Ordering::Less => pos += len + value_len(&buf[pos + len..])?,
+                Ordering::Equal => return Err(Error::DuplicateKey),
+                Ordering::Greater => {
+                    let entry_len = buf.len() - new;
+                    buf[pos..].rotate_right(entry_len);
+                    break;
+                }
+            }
+        }
         Ok(())
     }
-    fn _extend(&mut self, data: &[u8]) -> Result<(), Error> {
-        if data.len() + self.pos >= self.buf.len() {
-            return Err(Error::EndOfBuffer);
+    /// Writes the final header over the reserved room and drops the bytes the smaller header doesn't need
+    fn end_unsized(&mut self, start: HeaderFn, header: &UnsizedHeader) -> Result<(), Error> {
+        let mut buf = [0; MAX_ITEM_LEN];
+        let len = start(header.count, &mut buf)?;
+        self.writer.patch(header.pos, &buf[..len])?;
+        if len < header.len {
+            self.writer.remove(header.pos + len, header.len - len)?;
         }
-        self.buf[self.pos..].copy_from_slice(data);
-        self.pos += data.len();
         Ok(())
     }
 }
 
-impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
+type HeaderFn = fn(usize, &mut [u8]) -> Result<usize, Error>;
+
+/// Length of the encoded value at the start of `buf`
+fn value_len(buf: &[u8]) -> Result<usize, Error> {
+    crate::decode::skip_any(buf).map(|(_, len)| len).map_err(|_| Error::OutOfBounds)
+}
+
+/// Header of a sequence or map whose length was not known when it was started
+pub(crate) struct UnsizedHeader {
+    pos: usize,
+    len: usize,
+    count: usize,
+}
+
+impl<'a, W: Write, C: SerializerConfig> ser::Serializer for &'a mut Serializer<W, C> {
     type Ok = ();
     type Error = Error;
-    type SerializeSeq = SerializeSeq<'a, 'b>;
-    type SerializeTuple = SerializeSeq<'a, 'b>;
-    type SerializeTupleStruct = Unreachable;
-    type SerializeTupleVariant = &'a mut Serializer<'b>;
-    type SerializeMap = SerializeMap<'a, 'b>;
-    type SerializeStruct = SerializeStruct<'a, 'b>;
-    type SerializeStructVariant = &'a mut Serializer<'b>;
+    type SerializeSeq = SerializeSeq<'a, W, C>;
+    type SerializeTuple = SerializeSeq<'a, W, C>;
+    type SerializeTupleStruct = SerializeSeq<'a, W, C>;
+    type SerializeTupleVariant = &'a mut Serializer<W, C>;
+    type SerializeMap = SerializeMap<'a, W, C>;
+    type SerializeStruct = SerializeStruct<'a, W, C>;
+    type SerializeStructVariant = SerializeStruct<'a, W, C>;
 
     fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
         self.append(v)
@@ -103,10 +218,31 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
     fn serialize_u64(self, _v: u64) -> Result<Self::Ok, Self::Error> {
         unimplemented!()
     }
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+        self.append(v)
+    }
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+        self.append(v)
+    }
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        let v = if self.config.canonical() && v.is_nan() { f32::NAN } else { v };
+        if self.config.compact_floats() && self.append_integral(f64::from(v))? {
+            return Ok(());
+        }
         self.append(v)
     }
+    #[allow(clippy::cast_possible_truncation)]
     fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        let v = if self.config.canonical() && v.is_nan() { f64::NAN } else { v };
+        if self.config.compact_floats() {
+            if self.append_integral(v)? {
+                return Ok(());
+            }
+            let f = v as f32;
+            if f64::from(f).to_bits() == v.to_bits() {
+                return self.append(f);
+            }
+        }
         self.append(v)
     }
 
@@ -117,7 +253,8 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
-        self.append(v)
+        self.writer.write_with(|buf| super::serialize_str_start(v.len(), buf))?;
+        self.writer.write(v.as_bytes())
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
@@ -126,13 +263,12 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
             State::Ext(typ) => {
                 let typ = typ.ok_or(Error::InvalidType)?;
                 self.state = State::Normal;
-                let ext = crate::Ext::new(typ, v);
-                self.pos += (&ext).write_into_slice(&mut self.buf[self.pos..])?;
-                Ok(())
+                self.writer.write_with(|buf| crate::ext::serialize_ext_start(typ, v.len(), buf))?;
+                self.writer.write(v)
             }
             _ => {
-                let v = super::Binary::new(v);
-                self.append(v)
+                self.writer.write_with(|buf| super::serialize_bin_start(v.len(), buf))?;
+                self.writer.write(v)
             }
         }
     }
@@ -157,8 +293,8 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
         self.append(())
     }
 
-    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str) -> Result<Self::Ok, Self::Error> {
-        self.serialize_str(variant)
+    fn serialize_unit_variant(self, _name: &'static str, variant_index: u32, variant: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_variant(variant_index, variant)
     }
 
     fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, v: &T) -> Result<Self::Ok, Self::Error>
@@ -171,44 +307,62 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
     fn serialize_newtype_variant<T: ?Sized>(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
         T: ser::Serialize,
     {
-        let mut seq = self.serialize_map(Some(1))?;
-        serde::ser::SerializeMap::serialize_key(&mut seq, variant)?;
-        serde::ser::SerializeMap::serialize_value(&mut seq, value)
+        // externally tagged as `{ variant: value }`
+        self.writer.write_with(|buf| super::serialize_map_start(1, buf))?;
+        self.serialize_variant(variant_index, variant)?;
+        value.serialize(self)
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        self.pos += crate::encode::serialize_array_start(len.ok_or(Error::InvalidType)?, &mut self.buf[self.pos..])?;
-        Ok(SerializeSeq::new(self))
+        if let Some(len) = len {
+            self.writer.write_with(|buf| super::serialize_array_start(len, buf))?;
+            Ok(SerializeSeq::new(self, None))
+        } else {
+            let header = self.begin_unsized(super::serialize_array_start, super::MAX_ARRAY_LEN)?;
+            Ok(SerializeSeq::new(self, Some(header)))
+        }
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
         self.serialize_seq(Some(len))
     }
 
-    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        unimplemented!()
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
     }
 
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        // externally tagged as `{ variant: [fields...] }`
+        self.writer.write_with(|buf| super::serialize_map_start(1, buf))?;
+        self.serialize_variant(variant_index, variant)?;
+        self.writer.write_with(|buf| super::serialize_array_start(len, buf))?;
         Ok(self)
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        self.pos += super::serialize_map_start(len.ok_or(Error::InvalidType)?, &mut self.buf[self.pos..])?;
-        Ok(SerializeMap::new(self))
+        if self.config.canonical() {
+            self.require_patch()?;
+        }
+        if let Some(len) = len {
+            self.writer.write_with(|buf| super::serialize_map_start(len, buf))?;
+            Ok(SerializeMap::new(self, None))
+        } else {
+            let header = self.begin_unsized(super::serialize_map_start, super::MAX_MAP_LEN)?;
+            Ok(SerializeMap::new(self, Some(header)))
+        }
     }
 
     fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct, Self::Error> {
@@ -218,37 +372,74 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
                 // special handling to support serializing MsgPack Ext type
                 // SerializeStruct will reset `self.serializing_ext` when `end()` is called on it
                 self.state = State::Ext(None);
+                Ok(SerializeStruct::new(self, None))
             }
             #[cfg(feature = "timestamp")]
             crate::timestamp::TYPE_NAME => {
                 self.state = State::Timestamp(None, None);
+                Ok(SerializeStruct::new(self, None))
             }
             _ => {
-                self.pos += super::serialize_map_start(len, &mut self.buf[self.pos..])?;
+                let header = self.struct_start(len)?;
+                Ok(SerializeStruct::new(self, header))
             }
         }
-        Ok(SerializeStruct::new(self))
     }
 
     fn serialize_struct_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        self.pos += super::serialize_map_start(len, &mut self.buf[self.pos..])?;
-        self.serialize_str(variant)?;
-        self.pos += super::serialize_array_start(len, &mut self.buf[self.pos..])?;
-        Ok(self)
+        // externally tagged as `{ variant: fields }`, the fields like those of a struct
+        self.writer.write_with(|buf| super::serialize_map_start(1, buf))?;
+        self.serialize_variant(variant_index, variant)?;
+        let header = self.struct_start(len)?;
+        Ok(SerializeStruct::new(self, header))
     }
 
+    /// Formats `value` straight into the output, the length of the string is patched in afterwards
     #[cfg(not(any(feature = "std", feature = "alloc")))]
-    fn collect_str<T: ?Sized>(self, _value: &T) -> Result<Self::Ok, Self::Error>
+    fn collect_str<T: ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
         T: core::fmt::Display,
     {
-        unreachable!()
+        use core::fmt::Write as _;
+
+        let mut header = self.begin_unsized(super::serialize_str_start, super::MAX_STR_LEN)?;
+        let mut out = StrWriter {
+            writer: &mut self.writer,
+            len: 0,
+            error: None,
+        };
+        if write!(out, "{}", value).is_err() {
+            // a failing `Display` implementation doesn't leave an error of the writer behind
+            return Err(out.error.unwrap_or(Error::CustomError));
+        }
+        header.count = out.len;
+        self.end_unsized(super::serialize_str_start, &header)
+    }
+}
+
+/// Formats into the output of the serializer, counting the bytes and keeping the error of the writer
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+struct StrWriter<'a, W> {
+    writer: &'a mut W,
+    len: usize,
+    error: Option<Error>,
+}
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+impl<W: Write> core::fmt::Write for StrWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if let Err(e) = self.writer.write(s.as_bytes()) {
+            self.error = Some(e);
+            return Err(core::fmt::Error);
+        }
+        self.len += s.len();
+        Ok(())
     }
 }
 
@@ -257,170 +448,174 @@ pub fn to_array<T>(value: &T, buf: &mut [u8]) -> Result<usize, Error>
 where
     T: ser::Serialize + ?Sized,
 {
-    let mut ser = Serializer::new(buf);
-    value.serialize(&mut ser)?;
-    Ok(ser.pos)
+    to_array_with_config(value, buf, DefaultConfig)
 }
 
-impl ser::StdError for Error {}
-
-impl ser::Error for Error {
-    fn custom<T>(_msg: T) -> Self
-    where
-        T: core::fmt::Display,
-    {
-        unimplemented!()
-    }
+/// Like [`to_array`], with the given serializer options
+pub fn to_array_with_config<T, C>(value: &T, buf: &mut [u8], config: C) -> Result<usize, Error>
+where
+    T: ser::Serialize + ?Sized,
+    C: SerializerConfig,
+{
+    to_writer_with_config(value, &mut SliceWriter::new(buf), config)
 }
 
-impl<'a, 'b> ::serde::ser::SerializeTupleVariant for &'a mut Serializer<'b> {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
-    where
-        T: ?Sized + serde::Serialize,
-    {
-        value.serialize(&mut **self)
-    }
-
-    fn end(self) -> Result<(), Error> {
-        self.state = State::Normal;
-        Ok(())
-    }
+/// Serializes the given data structure piece by piece through `buf`, for outputs smaller than the whole message
+///
+/// `flush` is called with `buf` every time it is full and once more with the rest at the end, see [`ChunkWriter`].
+/// The chunks add up to the bytes [`to_array`] would write. Returns the number of bytes written.
+pub fn to_chunks<T, F>(value: &T, buf: &mut [u8], flush: F) -> Result<usize, Error>
+where
+    T: ser::Serialize + ?Sized,
+    F: FnMut(&[u8]) -> Result<(), Error>,
+{
+    to_chunks_with_config(value, buf, flush, DefaultConfig)
 }
 
-impl<'a, 'b> ::serde::ser::SerializeStructVariant for &'a mut Serializer<'b> {
-    type Ok = ();
-    type Error = Error;
-
-    fn end(self) -> Result<(), Error> {
-        self.state = State::Normal;
-        Ok(())
-    }
-
-    fn skip_field(&mut self, key: &'static str) -> Result<(), Self::Error> {
-        let _ = key;
-        Ok(())
-    }
-
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<(), Self::Error>
-    where
-        T: serde::Serialize,
-    {
-        // key.serialize(&mut **self)?;
-        value.serialize(&mut **self)
-    }
+/// Like [`to_chunks`], with the given serializer options
+pub fn to_chunks_with_config<T, F, C>(value: &T, buf: &mut [u8], flush: F, config: C) -> Result<usize, Error>
+where
+    T: ser::Serialize + ?Sized,
+    F: FnMut(&[u8]) -> Result<(), Error>,
+    C: SerializerConfig,
+{
+    let mut writer = ChunkWriter::new(buf, flush);
+    to_writer_with_config(value, &mut writer, config)?;
+    writer.finish()
 }
 
-pub(crate) enum Unreachable {}
-
-impl ::serde::ser::SerializeTupleStruct for Unreachable {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T: ?Sized>(&mut self, _value: &T) -> Result<Self::Ok, Self::Error> {
-        unreachable!()
-    }
-
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        unreachable!()
-    }
+/// Computes the number of bytes [`to_array`] would write for the given data structure, without writing anything
+pub fn serialized_size<T>(value: &T) -> Result<usize, Error>
+where
+    T: ser::Serialize + ?Sized,
+{
+    serialized_size_with_config(value, DefaultConfig)
 }
 
-impl ::serde::ser::SerializeTupleVariant for Unreachable {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T: ?Sized>(&mut self, _value: &T) -> Result<Self::Ok, Self::Error> {
-        unreachable!()
-    }
-
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        unreachable!()
-    }
+/// Like [`serialized_size`], with the given serializer options
+///
+/// In canonical mode the entries of maps are not kept to compare them, so duplicate keys are not detected. The size
+/// is then computed for a map that [`to_array_with_config`] rejects with [`Error::DuplicateKey`].
+pub fn serialized_size_with_config<T, C>(value: &T, config: C) -> Result<usize, Error>
+where
+    T: ser::Serialize + ?Sized,
+    C: SerializerConfig,
+{
+    to_writer_with_config(value, &mut SizeCounter::new(), config)
 }
 
-impl ::serde::ser::SerializeMap for Unreachable {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_key<T: ?Sized>(&mut self, _key: &T) -> Result<Self::Ok, Self::Error>
-    where
-        T: ::serde::ser::Serialize,
-    {
-        unreachable!()
-    }
-
-    fn serialize_value<T: ?Sized>(&mut self, _value: &T) -> Result<Self::Ok, Self::Error>
-    where
-        T: ::serde::ser::Serialize,
-    {
-        unreachable!()
-    }
+/// Serializes the given data structure into `writer`
+///
+/// Returns the number of bytes written.
+pub fn to_writer<T, W>(value: &T, writer: &mut W) -> Result<usize, Error>
+where
+    T: ser::Serialize + ?Sized,
+    W: Write,
+{
+    to_writer_with_config(value, writer, DefaultConfig)
+}
 
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        unreachable!()
-    }
+/// Like [`to_writer`], with the given serializer options
+pub fn to_writer_with_config<T, W, C>(value: &T, writer: &mut W, config: C) -> Result<usize, Error>
+where
+    T: ser::Serialize + ?Sized,
+    W: Write,
+    C: SerializerConfig,
+{
+    let start = writer.position();
+    let mut ser = Serializer::new(writer, config);
+    value.serialize(&mut ser)?;
+    Ok(ser.writer.position() - start)
 }
 
-impl ::serde::ser::SerializeStructVariant for Unreachable {
-    type Ok = ();
-    type Error = Error;
+/// Serializes the given data structure into a newly allocated `Vec`
+#[cfg(any(feature = "alloc", feature = "std"))]
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>, Error>
+where
+    T: ser::Serialize + ?Sized,
+{
+    to_vec_with_config(value, DefaultConfig)
+}
 
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, _value: &T) -> Result<Self::Ok, Self::Error>
-    where
-        T: ::serde::ser::Serialize,
-    {
-        unreachable!()
-    }
+/// Like [`to_vec`], with the given serializer options
+#[cfg(any(feature = "alloc", feature = "std"))]
+pub fn to_vec_with_config<T, C>(value: &T, config: C) -> Result<Vec<u8>, Error>
+where
+    T: ser::Serialize + ?Sized,
+    C: SerializerConfig,
+{
+    let mut vec = Vec::new();
+    to_writer_with_config(value, &mut vec, config)?;
+    Ok(vec)
+}
 
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        unreachable!()
-    }
+/// Serializes the given data structure to the end of `vec`
+///
+/// Returns the number of bytes appended. On error `vec` keeps its original contents.
+#[cfg(any(feature = "alloc", feature = "std"))]
+pub fn append_to_vec<T>(value: &T, vec: &mut Vec<u8>) -> Result<usize, Error>
+where
+    T: ser::Serialize + ?Sized,
+{
+    let start = vec.len();
+    to_writer(value, vec).inspect_err(|_| vec.truncate(start))
 }
 
-impl ::serde::ser::SerializeTuple for Unreachable {
-    type Ok = ();
-    type Error = Error;
+impl ser::StdError for Error {}
 
-    fn serialize_element<T: ?Sized>(&mut self, _value: &T) -> Result<(), Self::Error>
+impl ser::Error for Error {
+    #[cfg_attr(not(feature = "custom-error-messages"), allow(unused_variables))]
+    fn custom<T>(msg: T) -> Self
     where
-        T: serde::Serialize,
+        T: core::fmt::Display,
     {
-        unreachable!()
-    }
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        unreachable!()
+        #[cfg(not(feature = "custom-error-messages"))]
+        {
+            Error::CustomError
+        }
+        #[cfg(all(not(feature = "std"), feature = "custom-error-messages"))]
+        {
+            use core::fmt::Write;
+
+            let mut string = heapless::String::new();
+            // a message that doesn't fit is cut off, the error of the truncating writer is expected then
+            let _ = write!(Truncating(&mut string), "{}", msg);
+            Error::CustomErrorWithMessage(string)
+        }
+        #[cfg(all(feature = "std", feature = "custom-error-messages"))]
+        {
+            Error::CustomErrorWithMessage(msg.to_string())
+        }
     }
 }
 
-impl ::serde::ser::SerializeSeq for Unreachable {
-    type Ok = ();
-    type Error = Error;
+/// Writes into a fixed capacity string, dropping everything from the first char that doesn't fit
+#[cfg(all(not(feature = "std"), feature = "custom-error-messages"))]
+pub(crate) struct Truncating<'a, const N: usize>(pub(crate) &'a mut heapless::String<N>);
 
-    fn serialize_element<T: ?Sized>(&mut self, _value: &T) -> Result<(), Self::Error>
-    where
-        T: serde::Serialize,
-    {
-        unreachable!()
-    }
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        unreachable!()
+#[cfg(all(not(feature = "std"), feature = "custom-error-messages"))]
+impl<const N: usize> core::fmt::Write for Truncating<'_, N> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            self.0.push(c).map_err(|_| core::fmt::Error)?;
+        }
+        Ok(())
     }
 }
 
-impl ::serde::ser::SerializeStruct for Unreachable {
+impl<W: Write, C: SerializerConfig> ::serde::ser::SerializeTupleVariant for &mut Serializer<W, C> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, _value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        unreachable!()
+        value.serialize(&mut **self)
     }
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        unreachable!()
+
+    fn end(self) -> Result<(), Error> {
+        self.state = State::Normal;
+        Ok(())
     }
 }
@@ -1,29 +1,39 @@
 use serde::ser;
 
-use super::{Error, Serializer};
+use super::{Error, Serializer, SerializerConfig, UnsizedHeader};
+use crate::encode::Write;
 
-pub struct SerializeSeq<'a, 'b> {
-    ser: &'a mut Serializer<'b>,
+pub struct SerializeSeq<'a, W, C> {
+    ser: &'a mut Serializer<W, C>,
+    header: Option<UnsizedHeader>,
 }
 
-impl<'a, 'b> SerializeSeq<'a, 'b> {
-    pub(crate) fn new(ser: &'a mut Serializer<'b>) -> Self { SerializeSeq { ser } }
+impl<'a, W, C> SerializeSeq<'a, W, C> {
+    pub(crate) const fn new(ser: &'a mut Serializer<W, C>, header: Option<UnsizedHeader>) -> Self { SerializeSeq { ser, header } }
 }
 
-impl<'a, 'b> ser::SerializeSeq for SerializeSeq<'a, 'b> {
+impl<'a, W: Write, C: SerializerConfig> ser::SerializeSeq for SerializeSeq<'a, W, C> {
     type Ok = ();
     type Error = Error;
 
     fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<Self::Ok, Self::Error>
     where T: ser::Serialize {
         value.serialize(&mut *self.ser)?;
+        if let Some(header) = &mut self.header {
+            header.count += 1;
+        }
         Ok(())
     }
 
-    fn end(self) -> Result<Self::Ok, Self::Error> { Ok(()) }
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        match self.header {
+            Some(header) => self.ser.end_unsized(crate::encode::serialize_array_start, &header),
+            None => Ok(()),
+        }
+    }
 }
 
-impl<'a, 'b> ser::SerializeTuple for SerializeSeq<'a, 'b> {
+impl<'a, W: Write, C: SerializerConfig> ser::SerializeTuple for SerializeSeq<'a, W, C> {
     type Ok = ();
     type Error = Error;
 
@@ -34,3 +44,15 @@ impl<'a, 'b> ser::SerializeTuple for SerializeSeq<'a, 'b> {
 
     fn end(self) -> Result<Self::Ok, Self::Error> { ser::SerializeSeq::end(self) }
 }
+
+impl<'a, W: Write, C: SerializerConfig> ser::SerializeTupleStruct for SerializeSeq<'a, W, C> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<Self::Ok, Self::Error>
+    where T: ?Sized + ser::Serialize {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> { ser::SerializeSeq::end(self) }
+}
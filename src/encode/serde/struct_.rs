@@ -1,17 +1,25 @@
 use serde::ser::{self, Serialize};
 
-use super::{Error, Serializer};
+use super::{Error, Serializer, SerializerConfig, StructRepr, UnsizedHeader};
+use crate::encode::Write;
 
-pub struct SerializeStruct<'a, 'b> {
-    ser: &'a mut Serializer<'b>,
+pub struct SerializeStruct<'a, W, C> {
+    ser: &'a mut Serializer<W, C>,
+    /// Set if the number of fields is only known at the end
+    header: Option<UnsizedHeader>,
+    /// Position of the first field
+    entries: usize,
     #[cfg(feature = "timestamp")]
     ts_ser: TimestampSerializer,
 }
 
-impl<'a, 'b> SerializeStruct<'a, 'b> {
-    pub(crate) fn new(ser: &'a mut Serializer<'b>) -> Self {
+impl<'a, W: Write, C> SerializeStruct<'a, W, C> {
+    pub(crate) fn new(ser: &'a mut Serializer<W, C>, header: Option<UnsizedHeader>) -> Self {
+        let entries = ser.writer.position();
         SerializeStruct {
             ser,
+            header,
+            entries,
             #[cfg(feature = "timestamp")]
             ts_ser: TimestampSerializer {
                 seconds: 0,
@@ -21,7 +29,7 @@ impl<'a, 'b> SerializeStruct<'a, 'b> {
     }
 }
 
-impl<'a, 'b> ser::SerializeStruct for SerializeStruct<'a, 'b> {
+impl<'a, W: Write, C: SerializerConfig> ser::SerializeStruct for SerializeStruct<'a, W, C> {
     type Ok = ();
     type Error = Error;
 
@@ -30,9 +38,22 @@ impl<'a, 'b> ser::SerializeStruct for SerializeStruct<'a, 'b> {
         T: ser::Serialize,
     {
         match self.ser.state {
+            super::State::Normal if self.ser.config.struct_repr() == StructRepr::Array => {
+                value.serialize(&mut *self.ser)?;
+            }
             super::State::Normal => {
+                if self.ser.config.skip_none_fields() && value.serialize(IsNone).unwrap_or(false) {
+                    return Ok(());
+                }
+                let start = self.ser.writer.position();
                 key.serialize(&mut *self.ser)?;
                 value.serialize(&mut *self.ser)?;
+                if let Some(header) = &mut self.header {
+                    header.count += 1;
+                }
+                if self.ser.config.canonical() {
+                    self.ser.sort_entry(self.entries, start)?;
+                }
             }
             #[cfg(feature = "ext")]
             super::State::Ext(_) => match key {
@@ -63,7 +84,10 @@ impl<'a, 'b> ser::SerializeStruct for SerializeStruct<'a, 'b> {
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
         match self.ser.state {
-            super::State::Normal => Ok(()),
+            super::State::Normal => match &self.header {
+                Some(header) => self.ser.end_unsized(crate::encode::serialize_map_start, header),
+                None => Ok(()),
+            },
             #[cfg(feature = "ext")]
             super::State::Ext(_) => {
                 self.ser.state = super::State::Normal;
@@ -86,6 +110,166 @@ impl<'a, 'b> ser::SerializeStruct for SerializeStruct<'a, 'b> {
     }
 }
 
+impl<W: Write, C: SerializerConfig> ser::SerializeStructVariant for SerializeStruct<'_, W, C> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        ser::SerializeStruct::end(self)
+    }
+}
+
+/// Tells whether a value serializes as `None`, without writing anything
+///
+/// Used to leave out `None` fields before their key is written. Any other value stops the probe at its first method,
+/// compound values by failing.
+struct IsNone;
+
+impl ser::Serializer for IsNone {
+    type Ok = bool;
+    type Error = Error;
+    type SerializeSeq = ser::Impossible<bool, Error>;
+    type SerializeTuple = ser::Impossible<bool, Error>;
+    type SerializeTupleStruct = ser::Impossible<bool, Error>;
+    type SerializeTupleVariant = ser::Impossible<bool, Error>;
+    type SerializeMap = ser::Impossible<bool, Error>;
+    type SerializeStruct = ser::Impossible<bool, Error>;
+    type SerializeStructVariant = ser::Impossible<bool, Error>;
+
+    fn serialize_none(self) -> Result<bool, Error> {
+        Ok(true)
+    }
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<bool, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_i8(self, _v: i8) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_i16(self, _v: i16) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_i32(self, _v: i32) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_i64(self, _v: i64) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_i128(self, _v: i128) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_u8(self, _v: u8) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_u16(self, _v: u16) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_u32(self, _v: u32) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_u64(self, _v: u64) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_u128(self, _v: u128) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_f32(self, _v: f32) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_f64(self, _v: f64) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_char(self, _v: char) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_str(self, _v: &str) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_some<T>(self, _value: &T) -> Result<bool, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(false)
+    }
+    fn serialize_unit(self) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, _variant: &'static str) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<bool, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(false)
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error::InvalidType)
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error::InvalidType)
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::InvalidType)
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::InvalidType)
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::InvalidType)
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(Error::InvalidType)
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::InvalidType)
+    }
+    #[cfg(not(any(feature = "std", feature = "alloc")))]
+    fn collect_str<T>(self, _value: &T) -> Result<bool, Error>
+    where
+        T: ?Sized + core::fmt::Display,
+    {
+        Ok(false)
+    }
+}
+
 #[cfg(feature = "timestamp")]
 struct TimestampSerializer {
     seconds: i64,
@@ -93,16 +277,16 @@ struct TimestampSerializer {
 }
 
 #[cfg(feature = "timestamp")]
-impl<'a> ::serde::ser::Serializer for &'a mut TimestampSerializer {
+impl ::serde::ser::Serializer for &mut TimestampSerializer {
     type Ok = ();
     type Error = Error;
-    type SerializeSeq = super::Unreachable;
-    type SerializeTuple = super::Unreachable;
-    type SerializeTupleStruct = super::Unreachable;
-    type SerializeTupleVariant = super::Unreachable;
-    type SerializeMap = super::Unreachable;
-    type SerializeStruct = super::Unreachable;
-    type SerializeStructVariant = super::Unreachable;
+    type SerializeSeq = ser::Impossible<(), Error>;
+    type SerializeTuple = ser::Impossible<(), Error>;
+    type SerializeTupleStruct = ser::Impossible<(), Error>;
+    type SerializeTupleVariant = ser::Impossible<(), Error>;
+    type SerializeMap = ser::Impossible<(), Error>;
+    type SerializeStruct = ser::Impossible<(), Error>;
+    type SerializeStructVariant = ser::Impossible<(), Error>;
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
         self.seconds = v;
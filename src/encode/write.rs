@@ -0,0 +1,336 @@
+use super::Error;
+
+#[cfg(any(feature = "alloc", feature = "std"))]
+extern crate alloc;
+#[cfg(any(feature = "alloc", feature = "std"))]
+use alloc::vec::Vec;
+
+/// Size of the scratch buffer handed out by [`Write::write_with`].
+///
+/// Large enough for every scalar (the largest being a 128-bit integer written as `bin 8`) and every
+/// str/bin/array/map/ext header.
+pub const MAX_ITEM_LEN: usize = 18;
+
+/// Output sink of the serializer
+pub trait Write {
+    /// Appends `data` to the output.
+    fn write(&mut self, data: &[u8]) -> Result<(), Error>;
+
+    /// Appends a single scalar or header produced by `f`.
+    ///
+    /// `f` is passed a buffer of at least [`MAX_ITEM_LEN`] bytes (or whatever is left of a fixed size output)
+    /// and returns how many bytes it filled, just like [`SerializeIntoSlice::write_into_slice`](super::SerializeIntoSlice).
+    fn write_with<F>(&mut self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+    {
+        let mut tmp = [0; MAX_ITEM_LEN];
+        let len = f(&mut tmp)?;
+        self.write(&tmp[..len])
+    }
+
+    /// Number of bytes written so far.
+    fn position(&self) -> usize;
+
+    /// Number of bytes that can still be written, if the output has a fixed size.
+    ///
+    /// Headers that are only filled in at the end reserve no more room than this, so the output can be exactly as
+    /// large as the encoded value.
+    fn remaining(&self) -> Option<usize> {
+        None
+    }
+
+    /// Overwrites already written bytes starting at `pos` with `data`.
+    ///
+    /// Used to fill in headers that are only known once their content was written, like the length of a sequence of
+    /// unknown size. Sinks that can't go back (e.g. a socket) fail with [`Error::NotSeekable`].
+    fn patch(&mut self, pos: usize, data: &[u8]) -> Result<(), Error> {
+        let _ = (pos, data);
+        Err(Error::NotSeekable)
+    }
+
+    /// Removes `len` already written bytes starting at `pos`, moving everything written after them forward.
+    ///
+    /// Sinks that can't go back fail with [`Error::NotSeekable`].
+    fn remove(&mut self, pos: usize, len: usize) -> Result<(), Error> {
+        let _ = (pos, len);
+        Err(Error::NotSeekable)
+    }
+
+    /// All bytes written so far, if the sink keeps them in memory.
+    ///
+    /// Used to reorder map entries in canonical mode.
+    fn written_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+}
+
+impl<W: Write> Write for &mut W {
+    #[inline(always)]
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        (**self).write(data)
+    }
+    #[inline(always)]
+    fn write_with<F>(&mut self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+    {
+        (**self).write_with(f)
+    }
+    #[inline(always)]
+    fn position(&self) -> usize {
+        (**self).position()
+    }
+    #[inline(always)]
+    fn remaining(&self) -> Option<usize> {
+        (**self).remaining()
+    }
+    #[inline(always)]
+    fn patch(&mut self, pos: usize, data: &[u8]) -> Result<(), Error> {
+        (**self).patch(pos, data)
+    }
+    #[inline(always)]
+    fn remove(&mut self, pos: usize, len: usize) -> Result<(), Error> {
+        (**self).remove(pos, len)
+    }
+    #[inline(always)]
+    fn written_mut(&mut self) -> Option<&mut [u8]> {
+        (**self).written_mut()
+    }
+}
+
+/// Writes into a fixed size slice, failing with [`Error::EndOfBuffer`] once it is full
+pub struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+    pub const fn new(buf: &'a mut [u8]) -> Self {
+        SliceWriter { buf, pos: 0 }
+    }
+}
+
+impl<'a> Write for SliceWriter<'a> {
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        let end = self.pos + data.len();
+        if end > self.buf.len() {
+            return Err(Error::EndOfBuffer);
+        }
+        self.buf[self.pos..end].copy_from_slice(data);
+        self.pos = end;
+        Ok(())
+    }
+    #[inline(always)]
+    fn write_with<F>(&mut self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<usize, Error>,
+    {
+        // serialize straight into the output, no need for a scratch buffer
+        self.pos += f(&mut self.buf[self.pos..])?;
+        Ok(())
+    }
+    #[inline(always)]
+    fn position(&self) -> usize {
+        self.pos
+    }
+    #[inline(always)]
+    fn remaining(&self) -> Option<usize> {
+        Some(self.buf.len() - self.pos)
+    }
+    fn patch(&mut self, pos: usize, data: &[u8]) -> Result<(), Error> {
+        let end = pos + data.len();
+        if end > self.pos {
+            return Err(Error::OutOfBounds);
+        }
+        self.buf[pos..end].copy_from_slice(data);
+        Ok(())
+    }
+    fn remove(&mut self, pos: usize, len: usize) -> Result<(), Error> {
+        if pos + len > self.pos {
+            return Err(Error::OutOfBounds);
+        }
+        self.buf.copy_within(pos + len..self.pos, pos);
+        self.pos -= len;
+        Ok(())
+    }
+    fn written_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.buf[..self.pos])
+    }
+}
+
+#[cfg(any(feature = "alloc", feature = "std"))]
+impl Write for Vec<u8> {
+    #[inline]
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+    #[inline(always)]
+    fn position(&self) -> usize {
+        self.len()
+    }
+    fn patch(&mut self, pos: usize, data: &[u8]) -> Result<(), Error> {
+        self.get_mut(pos..pos + data.len()).ok_or(Error::OutOfBounds)?.copy_from_slice(data);
+        Ok(())
+    }
+    fn remove(&mut self, pos: usize, len: usize) -> Result<(), Error> {
+        if pos + len > self.len() {
+            return Err(Error::OutOfBounds);
+        }
+        self.drain(pos..pos + len);
+        Ok(())
+    }
+    fn written_mut(&mut self) -> Option<&mut [u8]> {
+        Some(self)
+    }
+}
+
+/// Forwards the output to a [`std::io::Write`]
+///
+/// Written bytes are passed on right away, so options that rewrite already written output fail with
+/// [`Error::NotSeekable`].
+#[cfg(feature = "std")]
+pub struct IoWriter<W: std::io::Write> {
+    inner: W,
+    pos: usize,
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> IoWriter<W> {
+    pub const fn new(inner: W) -> Self {
+        IoWriter { inner, pos: 0 }
+    }
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Write for IoWriter<W> {
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.inner.write_all(data).map_err(Error::Io)?;
+        self.pos += data.len();
+        Ok(())
+    }
+    #[inline(always)]
+    fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+/// Writes through a fixed size buffer, handing it to a callback every time it is full
+///
+/// The serializer is suspended while `flush` processes a chunk, e.g. by sending it over a UART, and resumes with the
+/// emptied buffer afterwards. Every byte is encoded once, so a message of any size needs no memory beyond the chunk.
+/// [`ChunkWriter::finish`] hands out the last, partially filled chunk.
+///
+/// Flushed chunks can't be changed anymore, so options that rewrite already written output (skipping `None` fields,
+/// canonical mode and sequences of unknown length) fail with [`Error::NotSeekable`].
+pub struct ChunkWriter<'a, F> {
+    buf: &'a mut [u8],
+    len: usize,
+    flushed: usize,
+    flush: F,
+}
+
+impl<'a, F> ChunkWriter<'a, F>
+where
+    F: FnMut(&[u8]) -> Result<(), Error>,
+{
+    pub const fn new(buf: &'a mut [u8], flush: F) -> Self {
+        ChunkWriter {
+            buf,
+            len: 0,
+            flushed: 0,
+            flush,
+        }
+    }
+
+    /// Flushes the bytes written since the last full chunk and returns the number of bytes written in total
+    pub fn finish(mut self) -> Result<usize, Error> {
+        if self.len > 0 {
+            self.flush_chunk()?;
+        }
+        Ok(self.flushed)
+    }
+
+    fn flush_chunk(&mut self) -> Result<(), Error> {
+        (self.flush)(&self.buf[..self.len])?;
+        self.flushed += self.len;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+impl<F> Write for ChunkWriter<'_, F>
+where
+    F: FnMut(&[u8]) -> Result<(), Error>,
+{
+    fn write(&mut self, mut data: &[u8]) -> Result<(), Error> {
+        if self.buf.is_empty() {
+            return Err(Error::EndOfBuffer);
+        }
+        while !data.is_empty() {
+            // a full chunk is only flushed once there is more to write, the last one is left to `finish`
+            if self.len == self.buf.len() {
+                self.flush_chunk()?;
+            }
+            let n = data.len().min(self.buf.len() - self.len);
+            self.buf[self.len..self.len + n].copy_from_slice(&data[..n]);
+            self.len += n;
+            data = &data[n..];
+        }
+        Ok(())
+    }
+    fn write_with<G>(&mut self, f: G) -> Result<(), Error>
+    where
+        G: FnOnce(&mut [u8]) -> Result<usize, Error>,
+    {
+        // serialize straight into the chunk if any item fits, otherwise split it up through the scratch buffer
+        if self.buf.len() - self.len >= MAX_ITEM_LEN {
+            self.len += f(&mut self.buf[self.len..])?;
+            return Ok(());
+        }
+        let mut tmp = [0; MAX_ITEM_LEN];
+        let len = f(&mut tmp)?;
+        self.write(&tmp[..len])
+    }
+    #[inline(always)]
+    fn position(&self) -> usize {
+        self.flushed + self.len
+    }
+}
+
+/// Discards the output and only counts the number of bytes
+#[derive(Default)]
+pub struct SizeCounter {
+    len: usize,
+}
+
+impl SizeCounter {
+    pub const fn new() -> Self {
+        SizeCounter { len: 0 }
+    }
+}
+
+impl Write for SizeCounter {
+    #[inline(always)]
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.len += data.len();
+        Ok(())
+    }
+    #[inline(always)]
+    fn position(&self) -> usize {
+        self.len
+    }
+    #[inline(always)]
+    fn patch(&mut self, _pos: usize, _data: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+    #[inline(always)]
+    fn remove(&mut self, _pos: usize, len: usize) -> Result<(), Error> {
+        self.len -= len;
+        Ok(())
+    }
+}
@@ -132,27 +132,34 @@ pub(crate) fn read_ext_len<B: zerocopy::ByteSlice>(buf: B) -> Result<(usize, usi
 }
 
 pub fn serialize_ext(value: &Ext<'_>, buf: &mut [u8]) -> Result<usize, Error> {
-    let typ = value.get_type();
     let data = value.get_data();
-
-    let (marker, header_len) = get_ext_start(data.len())?;
+    let header_len = serialize_ext_start(value.get_type(), data.len(), buf)?;
     if buf.len() < data.len() + header_len {
         return Err(Error::EndOfBuffer);
     }
+    buf[header_len..data.len() + header_len].clone_from_slice(data);
+    Ok(data.len() + header_len)
+}
+
+/// Writes the marker, length and type of an ext value with `data_len` bytes of data. The data has to follow.
+pub fn serialize_ext_start(typ: i8, data_len: usize, buf: &mut [u8]) -> Result<usize, Error> {
+    let (marker, header_len) = get_ext_start(data_len)?;
+    if buf.len() < header_len {
+        return Err(Error::EndOfBuffer);
+    }
     buf[0] = marker.to_u8();
     if header_len > 2 {
         #[cfg(all(feature = "ext8", not(any(feature = "ext16", feature = "ext32"))))]
         {
-            buf[1] = data.len() as u8;
+            buf[1] = data_len as u8;
         }
         #[cfg(any(feature = "ext16", feature = "ext32"))]
         {
-            BigEndian::write_uint(&mut buf[1..], data.len() as u64, header_len - 2);
+            BigEndian::write_uint(&mut buf[1..], data_len as u64, header_len - 2);
         }
     }
     buf[header_len - 1] = typ as u8;
-    buf[header_len..data.len() + header_len].clone_from_slice(data);
-    Ok(data.len() + header_len)
+    Ok(header_len)
 }
 
 pub fn try_deserialize_ext(buf: &[u8]) -> Result<Ext<'_>, crate::decode::Error> {
@@ -286,7 +293,7 @@ impl<'de: 'a, 'a> ::serde::de::Deserialize<'de> for Ext<'a> {
                         Err(_e) => {
                             // Error, could be an unknown field name
                             // println!("{:?}", e);
-                            map.next_value()?;
+                            map.next_value::<::serde::de::IgnoredAny>()?;
                         }
                     }
                 }
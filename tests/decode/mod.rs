@@ -35,6 +35,32 @@ fn decode_uint() {
     test_decode(256u16, &[&[0xcd, 0x01, 0x00], &[0xce, 0x00, 0x00, 0x01, 0x00]]);
 }
 #[test]
+fn decode_int128() {
+    test_decode(1u128, &[&[0x01], &[0xcc, 0x01], &[0xc4, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01]]);
+    test_decode(-2i128, &[&[0xfe], &[0xd0, 0xfe], &[&[0xc4, 0x10][..], &[0xff; 15], &[0xfe]].concat()]);
+    test_decode(u128::MAX, &[&[&[0xc4, 0x10][..], &[0xff; 16]].concat()]);
+    assert!(matches!(
+        wasm_msgpack::decode::read_u128(&[0xc4, 0x02, 0x01, 0x02][..]),
+        Err(wasm_msgpack::decode::Error::InvalidBinType)
+    ));
+    assert!(matches!(wasm_msgpack::decode::read_u128(&[0xff][..]), Err(wasm_msgpack::decode::Error::OutOfBounds)));
+
+    let mut v: Option<i128> = None;
+    assert_eq!(wasm_msgpack::decode::DeserializeFromSlice::from_slice(&mut v, &[0xd0, 0x80]).unwrap(), 2);
+    assert_eq!(v, Some(-128));
+}
+#[test]
+fn decode_float() {
+    test_decode(0.5f32, &[&[0xca, 0x3f, 0x00, 0x00, 0x00]]);
+    test_decode(0.5f64, &[&[0xcb, 0x3f, 0xe0, 0, 0, 0, 0, 0, 0], &[0xca, 0x3f, 0x00, 0x00, 0x00]]);
+    // integral values written as integers
+    test_decode(5f32, &[&[0x05], &[0xcc, 0x05], &[0xd0, 0x05], &[0xd3, 0, 0, 0, 0, 0, 0, 0, 0x05]]);
+    test_decode(-3f64, &[&[0xfd], &[0xd0, 0xfd], &[0xd2, 0xff, 0xff, 0xff, 0xfd]]);
+    test_decode(70000f64, &[&[0xce, 0x00, 0x01, 0x11, 0x70], &[0xcf, 0, 0, 0, 0, 0x00, 0x01, 0x11, 0x70]]);
+    assert_eq!(wasm_msgpack::decode::read_f32(&[0xcd, 0x01, 0x00][..]).unwrap(), (256f32, 3));
+    assert_eq!(wasm_msgpack::decode::read_f64(&[0xcf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff][..]).unwrap(), (u64::MAX as f64, 9));
+}
+#[test]
 fn decode_bin() {
     test_decode(
         wasm_msgpack::Bytes::new(&[
@@ -100,7 +126,11 @@ enum Test {
 fn decode_enum_unitvariant() {
     test_decode(
         Test::UnitVariant,
-        &[&[0xAB, 0x55, 0x6E, 0x69, 0x74, 0x56, 0x61, 0x72, 0x69, 0x61, 0x6E, 0x74]],
+        &[
+            &[0xAB, 0x55, 0x6E, 0x69, 0x74, 0x56, 0x61, 0x72, 0x69, 0x61, 0x6E, 0x74],
+            // by variant index
+            &[0x00],
+        ],
     );
 }
 
@@ -108,7 +138,12 @@ fn decode_enum_unitvariant() {
 fn decode_enum_newtypevariant() {
     test_decode(
         Test::NewTypeVariant(1),
-        &[&[129, 174, 78, 101, 119, 84, 121, 112, 101, 86, 97, 114, 105, 97, 110, 116, 1]],
+        &[
+            &[129, 174, 78, 101, 119, 84, 121, 112, 101, 86, 97, 114, 105, 97, 110, 116, 1],
+            // by variant index
+            &[0x81, 0x01, 0x01],
+            &[0x81, 0xcc, 0x01, 0x01],
+        ],
     );
 }
 
@@ -116,9 +151,14 @@ fn decode_enum_newtypevariant() {
 fn decode_enum_tuplevariant() {
     test_decode(
         Test::TupleVariant(1, 2),
-        &[&[
-            0xAC, 0x54, 0x75, 0x70, 0x6C, 0x65, 0x56, 0x61, 0x72, 0x69, 0x61, 0x6E, 0x74, 0x92, 0x01, 0x02,
-        ]],
+        &[
+            &[
+                0xAC, 0x54, 0x75, 0x70, 0x6C, 0x65, 0x56, 0x61, 0x72, 0x69, 0x61, 0x6E, 0x74, 0x92, 0x01, 0x02,
+            ],
+            &[
+                0x81, 0xAC, 0x54, 0x75, 0x70, 0x6C, 0x65, 0x56, 0x61, 0x72, 0x69, 0x61, 0x6E, 0x74, 0x92, 0x01, 0x02,
+            ],
+        ],
     );
 }
 
@@ -130,7 +170,9 @@ fn decode_enum_structvariant() {
             // old impl with serialized keys:
             //   &[0xAD, 0x53, 0x74, 0x72, 0x75, 0x63, 0x74, 0x56, 0x61, 0x72, 0x69, 0x61, 0x6E, 0x74, 0x82, 0xA1, 0x61, 0x01, 0xA1, 0x62, 0x02]
             // new impl serializing a tuple:
-            &[130, 173, 83, 116, 114, 117, 99, 116, 86, 97, 114, 105, 97, 110, 116, 146, 1, 2],
+            &[129, 173, 83, 116, 114, 117, 99, 116, 86, 97, 114, 105, 97, 110, 116, 146, 1, 2],
+            // by variant index
+            &[0x81, 0x03, 0x92, 0x01, 0x02],
         ],
     );
     #[derive(Deserialize_repr, Serialize, PartialEq, Eq, Debug)]
@@ -147,3 +189,639 @@ fn decode_enum_structvariant() {
     // .unwrap();
     // println!("{:?}", v);
 }
+
+#[test]
+fn decode_enum_externally_tagged() {
+    // `{ variant: payload }` with a map16/map32 header
+    #[cfg(feature = "map16")]
+    {
+        test_decode(Test::UnitVariant, &[&[0xde, 0x00, 0x01, 0x00, 0xc0]]);
+        test_decode(Test::NewTypeVariant(1), &[&[0xde, 0x00, 0x01, 0x01, 0x01]]);
+        test_decode(Test::TupleVariant(1, 2), &[&[0xde, 0x00, 0x01, 0x02, 0x92, 0x01, 0x02]]);
+        test_decode(
+            Test::StructVariant { a: 1, b: 2 },
+            &[&[0xde, 0x00, 0x01, 0x03, 0x82, 0xa1, b'b', 0x02, 0xa1, b'a', 0x01]],
+        );
+    }
+    #[cfg(feature = "map32")]
+    test_decode(Test::StructVariant { a: 1, b: 2 }, &[&[0xdf, 0x00, 0x00, 0x00, 0x01, 0x03, 0x92, 0x01, 0x02]]);
+    // struct variant with its fields as a map
+    test_decode(
+        Test::StructVariant { a: 1, b: 2 },
+        &[
+            &[0x81, 0x03, 0x82, 0xa1, b'a', 0x01, 0xa1, b'b', 0x02],
+            &[0x81, 0x03, 0x82, 0xa1, b'b', 0x02, 0xa1, b'a', 0x01],
+            &[0x03, 0x82, 0xa1, b'a', 0x01, 0xa1, b'b', 0x02],
+        ],
+    );
+    // unit variant in the map form
+    test_decode(Test::UnitVariant, &[&[0x81, 0x00, 0xc0], &[0x81, 0x00, 0x90]]);
+    // the whole enum nested in other values
+    test_decode(
+        vec![Test::UnitVariant, Test::NewTypeVariant(-1), Test::StructVariant { a: 1, b: 2 }],
+        &[&[0x93, 0x00, 0x81, 0x01, 0xff, 0x81, 0x03, 0x82, 0xa1, b'a', 0x01, 0xa1, b'b', 0x02]],
+    );
+
+    use wasm_msgpack::decode::{from_slice, Error};
+    // a map with anything but one entry doesn't tag a variant
+    assert!(matches!(from_slice::<Test>(&[0x80]), Err(Error::InvalidMapType)));
+    assert!(matches!(from_slice::<Test>(&[0x82, 0x01, 0x01, 0x01, 0x02]), Err(Error::InvalidMapType)));
+    // the value of a unit variant has to be empty
+    assert!(matches!(from_slice::<Test>(&[0x81, 0x00, 0x01]), Err(Error::InvalidType)));
+    assert!(matches!(from_slice::<Test>(&[0x81, 0x00]), Err(Error::EndOfBuffer(_))));
+    assert!(matches!(from_slice::<Test>(&[0x81, 0x02, 0x93, 0x01, 0x02, 0x03]), Err(Error::OutOfBounds)));
+}
+
+#[test]
+fn decode_struct_map_or_array() {
+    #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct Point {
+        x: u8,
+        y: i8,
+    }
+    let map: &[u8] = &[0x82, 0xa1, b'x', 0x01, 0xa1, b'y', 0xff];
+    let array: &[u8] = &[0x92, 0x01, 0xff];
+    test_decode(Point { x: 1, y: -1 }, &[map, array]);
+}
+
+#[test]
+fn decode_struct_repr_option() {
+    use wasm_msgpack::decode::{from_slice_with_config, DeserializerOptions, Error};
+    use wasm_msgpack::encode::serde::StructRepr;
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    struct Point {
+        x: u8,
+        y: i8,
+    }
+    let map: &[u8] = &[0x82, 0xa1, b'x', 0x01, 0xa1, b'y', 0xff];
+    let array: &[u8] = &[0x92, 0x01, 0xff];
+    let only_maps = DeserializerOptions::new().struct_repr(Some(StructRepr::Map));
+    let only_arrays = DeserializerOptions::new().struct_repr(Some(StructRepr::Array));
+    assert_eq!(from_slice_with_config::<Point, _>(map, only_maps).unwrap(), Point { x: 1, y: -1 });
+    assert!(matches!(from_slice_with_config::<Point, _>(array, only_maps), Err(Error::InvalidMapType)));
+    assert_eq!(from_slice_with_config::<Point, _>(array, only_arrays).unwrap(), Point { x: 1, y: -1 });
+    assert!(matches!(from_slice_with_config::<Point, _>(map, only_arrays), Err(Error::InvalidArrayType)));
+}
+
+#[test]
+fn decode_is_canonical() {
+    use wasm_msgpack::decode::{is_canonical, MAX_CANONICAL_DEPTH};
+    assert!(is_canonical(&[0x05]));
+    assert!(is_canonical(&[0xcc, 0x80]));
+    assert!(is_canonical(&[0xd0, 0xdf]));
+    assert!(is_canonical(&[0xa1, b'a']));
+    assert!(is_canonical(&[0x92, 0x01, 0x81, 0x01, 0xc0]));
+    assert!(is_canonical(&[0x82, 0x05, 0xc3, 0xa1, b'a', 0xc2]));
+    assert!(is_canonical(&[0xca, 0x7f, 0xc0, 0x00, 0x00]));
+
+    // larger markers than needed
+    assert!(!is_canonical(&[0xcc, 0x05]));
+    assert!(!is_canonical(&[0xd0, 0x05]));
+    assert!(!is_canonical(&[0xd0, 0xff]));
+    assert!(!is_canonical(&[0xd1, 0xff, 0xc0]));
+    assert!(!is_canonical(&[0xd9, 0x01, b'a']));
+    assert!(!is_canonical(&[0xc5, 0x00, 0x01, 0x00]));
+    assert!(!is_canonical(&[0x91, 0xcd, 0x00, 0x01]));
+    // NaN with payload
+    assert!(!is_canonical(&[0xca, 0x7f, 0xc0, 0x00, 0x01]));
+    // unsorted and duplicate keys
+    assert!(!is_canonical(&[0x82, 0xa1, b'a', 0xc2, 0x05, 0xc3]));
+    assert!(!is_canonical(&[0x82, 0x05, 0xc3, 0x05, 0xc2]));
+    assert!(!is_canonical(&[0x91, 0x82, 0x05, 0xc3, 0x05, 0xc2]));
+    // truncated, trailing bytes and reserved markers
+    assert!(!is_canonical(&[]));
+    assert!(!is_canonical(&[0x92, 0x01]));
+    assert!(!is_canonical(&[0xa2, b'a']));
+    assert!(!is_canonical(&[0x01, 0x02]));
+    assert!(!is_canonical(&[0xc1]));
+    assert!(!is_canonical(&[0xdd, 0xff, 0xff, 0xff, 0xff]));
+
+    // keys holding arrays and maps are compared as a whole, nested maps on their own
+    assert!(is_canonical(&[0x82, 0x91, 0x01, 0xc0, 0x91, 0x02, 0x81, 0x01, 0xc0]));
+    assert!(!is_canonical(&[0x82, 0x91, 0x02, 0xc0, 0x91, 0x01, 0xc0]));
+    assert!(is_canonical(&[0x82, 0x01, 0x82, 0x02, 0xc0, 0x03, 0xc0, 0x02, 0x81, 0x01, 0xc0]));
+    assert!(!is_canonical(&[0x82, 0x01, 0x82, 0x03, 0xc0, 0x02, 0xc0, 0x02, 0xc0]));
+    assert!(!is_canonical(&[0x82, 0x01, 0x81, 0x01, 0xc0, 0x01, 0xc0]));
+
+    // deep nesting takes a single pass
+    let mut deep = Vec::new();
+    for _ in 0..30_000 {
+        deep.extend_from_slice(&[0x81, 0x01]);
+    }
+    deep.push(0xc0);
+    let start = std::time::Instant::now();
+    assert_eq!(is_canonical(&deep), cfg!(any(feature = "alloc", feature = "std")));
+    assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    assert!(is_canonical(&deep[deep.len() - 2 * MAX_CANONICAL_DEPTH - 1..]));
+}
+
+#[test]
+fn decode_partial() {
+    use wasm_msgpack::decode::{from_slice, from_slice_partial, from_slice_strict, Error};
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    struct Point {
+        x: u8,
+        y: i8,
+    }
+    // two messages back to back, the second one with a field that isn't known
+    let buf: &[u8] = &[
+        0x82, 0xa1, b'x', 0x01, 0xa1, b'y', 0xff, //
+        0x83, 0xa1, b'x', 0x02, 0xa1, b'z', 0x92, 0xa1, b'a', 0xc0, 0xa1, b'y', 0x03, //
+        0xc0,
+    ];
+    let (first, len) = from_slice_partial::<Point>(buf).unwrap();
+    assert_eq!((first, len), (Point { x: 1, y: -1 }, 7));
+    let (second, len2) = from_slice_partial::<Point>(&buf[len..]).unwrap();
+    assert_eq!((second, len2), (Point { x: 2, y: 3 }, 13));
+    assert_eq!(from_slice_partial::<Option<u8>>(&buf[len + len2..]).unwrap(), (None, 1));
+
+    assert_eq!(from_slice::<Point>(buf).unwrap(), Point { x: 1, y: -1 });
+    assert!(matches!(from_slice_strict::<Point>(buf), Err(Error::TrailingBytes)));
+    assert_eq!(from_slice_strict::<Point>(&buf[..len]).unwrap(), Point { x: 1, y: -1 });
+    assert!(matches!(from_slice_strict::<Point>(&buf[..len - 1]), Err(Error::EndOfBuffer(_))));
+}
+
+#[test]
+fn decode_trailing_elements() {
+    use wasm_msgpack::decode::{from_slice, from_slice_partial, Error};
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    struct Point {
+        x: u8,
+        y: i8,
+    }
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    struct Line {
+        from: Point,
+        to: Point,
+    }
+    // a struct with two fields from an array of three values
+    let buf: &[u8] = &[0x93, 0x01, 0x02, 0x03];
+    assert!(matches!(from_slice_partial::<Point>(buf), Err(Error::TrailingElements)));
+    assert!(matches!(from_slice::<(u8, u8)>(buf), Err(Error::TrailingElements)));
+    assert_eq!(from_slice_partial::<(u8, u8, u8)>(buf).unwrap(), ((1, 2, 3), 4));
+    // the extra value doesn't shift the fields that follow
+    let buf: &[u8] = &[0x92, 0x93, 0x01, 0x02, 0x03, 0x92, 0x04, 0x05];
+    assert!(matches!(from_slice::<Line>(buf), Err(Error::TrailingElements)));
+    let buf: &[u8] = &[0x92, 0x92, 0x01, 0x02, 0x92, 0x04, 0x05];
+    assert_eq!(
+        from_slice::<Line>(buf).unwrap(),
+        Line {
+            from: Point { x: 1, y: 2 },
+            to: Point { x: 4, y: 5 }
+        }
+    );
+}
+
+#[test]
+fn decode_stream() {
+    use wasm_msgpack::decode::{Error, StreamDeserializer};
+    #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct Entry<'a> {
+        id: u32,
+        text: &'a str,
+    }
+    let entries = [Entry { id: 1, text: "first" }, Entry { id: 300, text: "second" }, Entry { id: 70000, text: "" }];
+    let mut buf = [0u8; 100];
+    let mut len = 0;
+    let mut last = 0;
+    for entry in &entries {
+        last = len;
+        len += wasm_msgpack::encode::serde::to_array(entry, &mut buf[len..]).unwrap();
+    }
+    let buf = &buf[..len];
+
+    let decoded: Vec<Entry> = StreamDeserializer::new(buf).collect::<Result<_, _>>().unwrap();
+    assert_eq!(decoded, entries);
+    assert_eq!(StreamDeserializer::<u8>::new(&[]).count(), 0);
+
+    // the last message is cut off anywhere
+    for end in last + 1..buf.len() {
+        let mut stream = StreamDeserializer::<Entry>::new(&buf[..end]);
+        assert_eq!(stream.next().unwrap().unwrap(), entries[0]);
+        assert_eq!(stream.next().unwrap().unwrap(), entries[1]);
+        assert!(matches!(stream.next(), Some(Err(Error::EndOfBuffer(_)))));
+        assert!(stream.next().is_none());
+        assert_eq!(stream.position(), last);
+        assert_eq!(stream.remaining(), &buf[last..end]);
+    }
+
+    // corrupt data stops the iteration as well
+    let mut stream = StreamDeserializer::<Entry>::new(&[0x01, 0xc0]);
+    assert!(matches!(stream.next(), Some(Err(Error::InvalidMapType))));
+    assert!(stream.next().is_none());
+    // a complete value of the wrong type isn't mistaken for a cut off one
+    let mut stream = StreamDeserializer::<u32>::new(&[0x01, 0xa1, b'x', 0x02]);
+    assert_eq!(stream.next().unwrap().unwrap(), 1);
+    assert!(matches!(stream.next(), Some(Err(Error::InvalidType))));
+    assert!(stream.next().is_none());
+    assert_eq!(stream.remaining(), &[0xa1, b'x', 0x02]);
+    let mut stream = StreamDeserializer::<f64>::new(&[0x01, 0xc0]);
+    assert_eq!(stream.next().unwrap().unwrap(), 1.0);
+    assert!(matches!(stream.next(), Some(Err(Error::InvalidType))));
+    // a type that doesn't take its whole value fails instead of misaligning the stream
+    struct Nothing;
+    impl<'de> Deserialize<'de> for Nothing {
+        fn deserialize<D: serde::Deserializer<'de>>(_deserializer: D) -> Result<Self, D::Error> {
+            Ok(Nothing)
+        }
+    }
+    let mut stream = StreamDeserializer::<Nothing>::new(&[0x91, 0x01, 0x02]);
+    assert!(matches!(stream.next(), Some(Err(Error::TrailingBytes))));
+    assert!(stream.next().is_none());
+    // but a cut off value of the wrong type still is
+    let mut stream = StreamDeserializer::<u32>::new(&[0x01, 0xa3, b'x']);
+    assert_eq!(stream.next().unwrap().unwrap(), 1);
+    assert!(matches!(stream.next(), Some(Err(Error::EndOfBuffer(_)))));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn decode_from_reader() {
+    use std::collections::BTreeMap;
+    use wasm_msgpack::decode::{from_reader, Error};
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    struct Message {
+        id: u64,
+        name: String,
+        tags: Vec<String>,
+        data: BTreeMap<String, f64>,
+        payload: Option<(i8, bool)>,
+    }
+    let messages = [
+        Message {
+            id: 1,
+            name: "a name that takes more than 32 bytes".into(),
+            tags: vec!["x".into(), "y".into()],
+            data: [("a".into(), 0.5), ("b".into(), -1.0)].into_iter().collect(),
+            payload: Some((-7, true)),
+        },
+        Message {
+            id: 1 << 40,
+            name: String::new(),
+            tags: vec![],
+            data: BTreeMap::new(),
+            payload: None,
+        },
+    ];
+    let mut bytes = Vec::new();
+    for message in &messages {
+        bytes.extend_from_slice(&wasm_msgpack::encode::serde::to_vec(message).unwrap());
+    }
+    bytes.push(0x05);
+
+    // values are read one after another from the same reader
+    let mut reader = &bytes[..];
+    assert_eq!(from_reader::<_, Message>(&mut reader).unwrap(), messages[0]);
+    assert_eq!(from_reader::<_, Message>(&mut reader).unwrap(), messages[1]);
+    assert_eq!(reader, &[0x05]);
+    assert_eq!(from_reader::<_, u8>(&mut reader).unwrap(), 5);
+    assert!(reader.is_empty());
+
+    // ends inside a value
+    assert!(matches!(from_reader::<_, Message>(&bytes[..bytes.len() / 2]), Err(Error::EndOfBuffer(_))));
+    assert!(matches!(from_reader::<_, String>(&[0xdb, 0xff, 0xff, 0xff, 0xff, b'a'][..]), Err(Error::EndOfBuffer(_))));
+    assert!(matches!(from_reader::<_, u8>(&[][..]), Err(Error::EndOfBuffer(_))));
+
+    struct Failing;
+    impl std::io::Read for Failing {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("broken pipe"))
+        }
+    }
+    let err = from_reader::<_, u8>(Failing).unwrap_err();
+    assert_eq!(err.to_string(), "I/O error: broken pipe");
+
+    // any reader works, buffered or not
+    let mut reader = std::io::BufReader::with_capacity(4, &bytes[..]);
+    assert_eq!(from_reader::<_, Message>(&mut reader).unwrap(), messages[0]);
+    assert_eq!(from_reader::<_, Message>(&mut reader).unwrap(), messages[1]);
+    assert_eq!(from_reader::<_, u8>(&mut reader).unwrap(), 5);
+    let mut source = &bytes[..];
+    let reader: &mut dyn std::io::Read = &mut source;
+    assert_eq!(from_reader::<_, Message>(reader).unwrap(), messages[0]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn decode_from_reader_limits() {
+    use std::io::{repeat, Read};
+    use wasm_msgpack::decode::{from_reader, from_reader_with_config, DeserializerOptions, Error, Limit, Limits};
+
+    // endless input is cut off by the depth and limits before it fills the memory
+    let endless = repeat(0x91);
+    assert!(matches!(from_reader::<_, u8>(endless), Err(Error::DepthLimitExceeded)));
+    let options = DeserializerOptions::new().max_depth(2);
+    assert_eq!(from_reader_with_config::<_, Vec<Vec<u8>>, _>(&[0x91, 0x91, 0x01][..], options).unwrap(), vec![vec![1]]);
+    assert!(matches!(
+        from_reader_with_config::<_, Vec<Vec<Vec<u8>>>, _>(&[0x91, 0x91, 0x91, 0x01][..], options),
+        Err(Error::DepthLimitExceeded)
+    ));
+
+    let limits = Limits::new().max_str_len(8).max_bin_len(8).max_map_entries(2).max_elements(16).max_alloc_bytes(10);
+    let options = DeserializerOptions::new().limits(limits);
+    let endless = |header: &'static [u8], byte| header.chain(repeat(byte));
+    let result = from_reader_with_config::<_, String, _>(endless(&[0xdb, 0xff, 0xff, 0xff, 0xff], b'a'), options);
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::StrLen))));
+    let result = from_reader_with_config::<_, Vec<u8>, _>(endless(&[0xc6, 0xff, 0xff, 0xff, 0xff], 0x00), options);
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::BinLen))));
+    let result = from_reader_with_config::<_, Vec<u8>, _>(endless(&[0xdd, 0xff, 0xff, 0xff, 0xff], 0x01), options);
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::Elements))));
+    let result = from_reader_with_config::<_, Vec<(u8, u8)>, _>(endless(&[0x83], 0x01), options);
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::MapEntries))));
+    // the sum of all strings counts, each of them is copied
+    let strings = [0x92, 0xa6, b'a', b'b', b'c', b'd', b'e', b'f', 0xa6, b'a', b'b', b'c', b'd', b'e', b'f'];
+    let result = from_reader_with_config::<_, Vec<String>, _>(&strings[..], options);
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::AllocBytes))));
+    let result = from_reader_with_config::<_, Vec<String>, _>(&strings[..], options.limits(Limits::new()));
+    assert_eq!(result.unwrap(), vec!["abcdef", "abcdef"]);
+}
+
+#[test]
+fn decode_push() {
+    use wasm_msgpack::decode::{Error, Progress, PushDecoder};
+    #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct Frame {
+        id: u16,
+        values: [u8; 3],
+    }
+    let frame = Frame { id: 500, values: [1, 2, 200] };
+    let mut bytes = [0u8; 64];
+    let len = wasm_msgpack::encode::serde::to_array(&frame, &mut bytes).unwrap();
+    let bytes = &bytes[..len];
+
+    // one byte at a time
+    let mut storage = [0u8; 32];
+    let mut decoder = PushDecoder::new(&mut storage);
+    for &byte in &bytes[..len - 1] {
+        assert_eq!(decoder.push(&[byte]), 1);
+        assert!(matches!(decoder.next_value::<Frame>(), Ok(Progress::NeedMore(n)) if n >= 1));
+    }
+    assert_eq!(decoder.push(&bytes[len - 1..]), 1);
+    assert_eq!(decoder.next_value::<Frame>().unwrap(), Progress::Value(Frame { id: 500, values: [1, 2, 200] }));
+    assert!(decoder.buffered().is_empty());
+    assert_eq!(decoder.next_value::<Frame>().unwrap(), Progress::NeedMore(1));
+
+    // the least number of missing bytes
+    let mut decoder = PushDecoder::new(&mut storage);
+    decoder.push(&[0x93, 0xcd, 0x01]);
+    assert_eq!(decoder.next_value::<[u16; 3]>().unwrap(), Progress::NeedMore(3));
+    decoder.push(&[0x02, 0xa5]);
+    assert_eq!(decoder.next_value::<Frame>().unwrap(), Progress::NeedMore(6));
+    let mut decoder = PushDecoder::new(&mut storage);
+    decoder.push(&[0x92, 0xd9]);
+    assert_eq!(decoder.next_value::<Frame>().unwrap(), Progress::NeedMore(2));
+    decoder.push(&[0x1a]);
+    assert_eq!(decoder.next_value::<Frame>().unwrap(), Progress::NeedMore(0x1a + 1));
+
+    // values larger than the buffer fail once their headers are in
+    let mut decoder = PushDecoder::new(&mut storage);
+    decoder.push(&[0x92, 0xdb, 0xff]);
+    assert_eq!(decoder.next_value::<Frame>().unwrap(), Progress::NeedMore(4));
+    decoder.push(&[0x00, 0x00, 0x00]);
+    let err = decoder.next_value::<Frame>().unwrap_err();
+    assert_eq!(format!("{err:?}"), "EndOfBuffer(Str32)");
+    let mut decoder = PushDecoder::new(&mut storage);
+    decoder.push(&[0xdc, 0x00, 0x40]);
+    assert!(matches!(decoder.next_value::<[u8; 3]>(), Err(Error::EndOfBuffer(_))));
+    // one that fits exactly is still waited for
+    let mut exact = [0u8; 31];
+    let mut decoder = PushDecoder::new(&mut exact);
+    decoder.push(&[0x9f, 0xcc]);
+    assert_eq!(decoder.next_value::<[u8; 15]>().unwrap(), Progress::NeedMore(15));
+    assert_eq!(decoder.push(&[0xcc; 15 * 2 - 1]), 15 * 2 - 1);
+    assert_eq!(decoder.next_value::<[u8; 15]>().unwrap(), Progress::Value([0xcc; 15]));
+
+    // two values in one chunk, the first one doesn't match the type
+    let mut decoder = PushDecoder::new(&mut storage);
+    assert_eq!(decoder.push(&[0xa1, b'a', 0x07, 0x08]), 4);
+    assert!(decoder.next_value::<u8>().is_err());
+    assert_eq!(decoder.next_value::<u8>().unwrap(), Progress::Value(7));
+    assert_eq!(decoder.buffered(), &[0x08]);
+
+    // the buffer is full
+    let mut small = [0u8; 4];
+    let mut decoder = PushDecoder::new(&mut small);
+    assert_eq!(decoder.push(bytes), 4);
+    assert_eq!(decoder.push(&bytes[4..]), 0);
+}
+
+#[test]
+fn decode_depth_limit() {
+    use serde::de::IgnoredAny;
+    use wasm_msgpack::decode::{from_slice, from_slice_with_config, skip_any, DeserializerOptions, Error, DEFAULT_MAX_DEPTH};
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut buf = vec![0x91; depth];
+        buf.push(0xc0);
+        buf
+    }
+    assert!(from_slice::<IgnoredAny>(&nested(DEFAULT_MAX_DEPTH)).is_ok());
+    assert!(matches!(from_slice::<IgnoredAny>(&nested(DEFAULT_MAX_DEPTH + 1)), Err(Error::DepthLimitExceeded)));
+    assert!(matches!(from_slice::<IgnoredAny>(&nested(1_000_000)), Err(Error::DepthLimitExceeded)));
+
+    // maps count as well
+    let options = DeserializerOptions::new().max_depth(2);
+    assert_eq!(from_slice_with_config::<Vec<Vec<u8>>, _>(&[0x91, 0x91, 0x01], options).unwrap(), vec![vec![1]]);
+    assert!(matches!(from_slice_with_config::<IgnoredAny, _>(&[0x91, 0x81, 0x01, 0x91, 0x01], options), Err(Error::DepthLimitExceeded)));
+    // the depth of siblings doesn't add up
+    assert_eq!(from_slice_with_config::<Vec<Vec<u8>>, _>(&[0x92, 0x91, 0x01, 0x90], options).unwrap(), vec![vec![1], vec![]]);
+
+    // so do the payloads of enum variants, in the bare form as well
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum Tree {
+        Leaf,
+        Node(Box<Tree>),
+    }
+    fn chain(depth: usize, bare: bool) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for _ in 0..depth {
+            if !bare {
+                buf.push(0x81);
+            }
+            buf.extend_from_slice(b"\xa4Node");
+        }
+        buf.extend_from_slice(b"\xa4Leaf");
+        buf
+    }
+    for bare in [true, false] {
+        let tree = from_slice::<Tree>(&chain(2, bare)).unwrap();
+        assert_eq!(tree, Tree::Node(Box::new(Tree::Node(Box::new(Tree::Leaf)))));
+        assert!(from_slice::<Tree>(&chain(DEFAULT_MAX_DEPTH - 1, bare)).is_ok());
+        assert!(matches!(from_slice::<Tree>(&chain(DEFAULT_MAX_DEPTH, bare)), Err(Error::DepthLimitExceeded)));
+        assert!(matches!(from_slice::<Tree>(&chain(200_000, bare)), Err(Error::DepthLimitExceeded)));
+    }
+
+    // skipping doesn't recurse at all
+    let deep = nested(1_000_000);
+    assert_eq!(skip_any(&deep[..]).unwrap().1, deep.len());
+    assert!(matches!(skip_any(&deep[..deep.len() - 1]), Err(Error::EndOfBuffer(_))));
+    assert!(matches!(skip_any(&[0x81, 0x01][..]), Err(Error::EndOfBuffer(_))));
+    // the error names the item that is cut off
+    for (bytes, marker) in [
+        (&[0xa3, b'a'][..], "FixStr(3)"),
+        (&[0x92, 0x01, 0xcd, 0x01], "U16"),
+        (&[0x91, 0xc7, 0x05, 0x01], "Ext8"),
+        (&[0x91, 0xdd, 0x00], "Array32"),
+        (&[0xc5, 0x00, 0x02, 0x00], "Bin16"),
+        (&[0x81, 0x01], "Reserved"),
+    ] {
+        let err = skip_any(bytes).unwrap_err();
+        assert_eq!(format!("{err:?}"), format!("EndOfBuffer({marker})"), "{bytes:02x?}");
+    }
+}
+
+#[test]
+fn decode_limits() {
+    use std::collections::BTreeMap;
+    use wasm_msgpack::decode::{from_slice, from_slice_with_config, DeserializerOptions, Error, Limit, Limits};
+    fn limited(limits: Limits) -> DeserializerOptions {
+        DeserializerOptions::new().limits(limits)
+    }
+    let strings: &[u8] = &[0x92, 0xa3, b'a', b'b', b'c', 0xc4, 0x02, 0x01, 0x02];
+    let map: &[u8] = &[0x82, 0x01, 0x91, 0x02, 0x03, 0x90];
+
+    // unlimited by default
+    assert!(from_slice::<(&str, &[u8])>(strings).is_ok());
+    let options = limited(Limits::new().max_str_len(3).max_bin_len(2).max_alloc_bytes(5).max_elements(5).max_map_entries(2));
+    assert!(from_slice_with_config::<(&str, &[u8]), _>(strings, options).is_ok());
+    assert!(from_slice_with_config::<BTreeMap<u8, Vec<u8>>, _>(map, options).is_ok());
+
+    let result = from_slice_with_config::<(&str, &[u8]), _>(strings, limited(Limits::new().max_str_len(2)));
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::StrLen))));
+    let result = from_slice_with_config::<(&str, &[u8]), _>(strings, limited(Limits::new().max_bin_len(1)));
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::BinLen))));
+    // only copies count towards the allocated bytes
+    #[derive(Debug, PartialEq)]
+    struct ByteBuf(Vec<u8>);
+    impl<'de> Deserialize<'de> for ByteBuf {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct Visitor;
+            impl serde::de::Visitor<'_> for Visitor {
+                type Value = ByteBuf;
+                fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+                    formatter.write_str("bytes")
+                }
+                fn visit_bytes<E>(self, v: &[u8]) -> Result<ByteBuf, E> {
+                    Ok(ByteBuf(v.to_vec()))
+                }
+            }
+            deserializer.deserialize_byte_buf(Visitor)
+        }
+    }
+    assert!(from_slice_with_config::<(&str, &[u8]), _>(strings, limited(Limits::new().max_alloc_bytes(0))).is_ok());
+    let result = from_slice_with_config::<(String, ByteBuf), _>(strings, limited(Limits::new().max_alloc_bytes(5)));
+    assert_eq!(result.unwrap(), ("abc".into(), ByteBuf(vec![1, 2])));
+    let result = from_slice_with_config::<(String, ByteBuf), _>(strings, limited(Limits::new().max_alloc_bytes(4)));
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::AllocBytes))));
+    let result = from_slice_with_config::<(&str, ByteBuf), _>(strings, limited(Limits::new().max_alloc_bytes(2)));
+    assert_eq!(result.unwrap(), ("abc", ByteBuf(vec![1, 2])));
+    let result = from_slice_with_config::<(String, &[u8]), _>(strings, limited(Limits::new().max_alloc_bytes(2)));
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::AllocBytes))));
+    // self-describing types copy everything they visit, skipped values nothing
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Untagged {
+        Text(String),
+    }
+    let text: &[u8] = &[0xa3, b'a', b'b', b'c'];
+    let result = from_slice_with_config::<Untagged, _>(text, limited(Limits::new().max_alloc_bytes(3)));
+    assert_eq!(result.unwrap(), Untagged::Text("abc".into()));
+    let result = from_slice_with_config::<Untagged, _>(text, limited(Limits::new().max_alloc_bytes(2)));
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::AllocBytes))));
+    let result = from_slice_with_config::<serde_json::Value, _>(strings, limited(Limits::new().max_alloc_bytes(4)));
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::AllocBytes))));
+    let result = from_slice_with_config::<serde::de::IgnoredAny, _>(strings, limited(Limits::new().max_alloc_bytes(0)));
+    assert!(result.is_ok());
+    // field names aren't copied either
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Named {
+        name: String,
+    }
+    let named: &[u8] = &[0x81, 0xa4, b'n', b'a', b'm', b'e', 0xa2, b'x', b'y'];
+    let result = from_slice_with_config::<Named, _>(named, limited(Limits::new().max_alloc_bytes(2)));
+    assert_eq!(result.unwrap(), Named { name: "xy".into() });
+    let result = from_slice_with_config::<BTreeMap<u8, Vec<u8>>, _>(map, limited(Limits::new().max_map_entries(1)));
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::MapEntries))));
+    // the elements of all arrays and maps add up
+    let result = from_slice_with_config::<BTreeMap<u8, Vec<u8>>, _>(map, limited(Limits::new().max_elements(2)));
+    assert!(matches!(result, Err(Error::LimitExceeded(Limit::Elements))));
+
+    // the header is checked before the content is read
+    let limits = Limits::new().max_elements(1000);
+    assert!(matches!(limits.max_elements(2).read_array_len(&[0x93, 0x01, 0x02, 0x03][..]), Err(Error::LimitExceeded(Limit::Elements))));
+    assert!(matches!(limits.max_str_len(10).read_str(&[0xa5, b'a'][..]), Err(Error::EndOfBuffer(_))));
+    assert_eq!(limits.read_map_len(&[0x81, 0x01, 0x02][..]).unwrap(), (1, 1));
+}
+
+#[test]
+fn decode_any() {
+    use serde::de::IgnoredAny;
+    use serde_json::{json, Value};
+    use wasm_msgpack::decode::{from_slice, Error};
+    assert_eq!(from_slice::<Value>(&[0x93, 0xc0, 0xff, 0xe0]).unwrap(), json!([null, -1, -32]));
+    assert_eq!(
+        from_slice::<Value>(&[0x83, 0xa1, b'a', 0xd0, 0x80, 0xa1, b'b', 0xc3, 0xa1, b'c', 0x92, 0xcc, 0xff, 0xcb, 0x3f, 0xe0, 0, 0, 0, 0, 0, 0]).unwrap(),
+        json!({"a": -128, "b": true, "c": [255, 0.5]})
+    );
+
+    // the reserved marker isn't valid anywhere
+    assert!(matches!(from_slice::<Value>(&[0xc1]), Err(Error::InvalidType)));
+    assert!(matches!(from_slice::<Value>(&[0x91, 0xc1]), Err(Error::InvalidType)));
+    assert!(matches!(from_slice::<IgnoredAny>(&[0x92, 0x01, 0xc1]), Err(Error::InvalidType)));
+}
+
+#[cfg(feature = "ext")]
+#[test]
+fn decode_any_ext() {
+    use wasm_msgpack::{decode::from_slice, timestamp::Timestamp, Ext};
+    // ext values are visited as their type and data, so untyped targets keep them
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(untagged)]
+    enum Any<'a> {
+        Int(i64),
+        #[serde(borrow)]
+        Ext(Ext<'a>),
+    }
+    assert_eq!(from_slice::<Any>(&[0xfe]).unwrap(), Any::Int(-2));
+    assert_eq!(from_slice::<Any>(&[0xd4, 0x0a, 0x2a]).unwrap(), Any::Ext(Ext::new(10, &[0x2a])));
+    assert_eq!(from_slice::<Any>(&[0xd5, 0x05, 0x01, 0x02]).unwrap(), Any::Ext(Ext::new(5, &[1, 2])));
+    assert_eq!(
+        from_slice::<Vec<Any>>(&[0x92, 0xc7, 0x00, 0x7f, 0xe1]).unwrap(),
+        vec![Any::Ext(Ext::new(127, &[])), Any::Int(-31)]
+    );
+
+    #[cfg(feature = "timestamp")]
+    {
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(untagged)]
+        enum Time {
+            Seconds(u32),
+            Timestamp(Timestamp),
+        }
+        let ts = [0xd6, 0xff, 0x00, 0x00, 0x01, 0x00];
+        assert_eq!(from_slice::<Time>(&ts).unwrap(), Time::Timestamp(Timestamp::new(256, 0).unwrap()));
+        assert_eq!(from_slice::<Time>(&[0x05]).unwrap(), Time::Seconds(5));
+    }
+
+    // a visitor has to take both the type and the data
+    struct TypeOnly;
+    impl<'de> Deserialize<'de> for TypeOnly {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct Visitor;
+            impl<'de> serde::de::Visitor<'de> for Visitor {
+                type Value = TypeOnly;
+                fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+                    formatter.write_str("an ext type")
+                }
+                fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<TypeOnly, A::Error> {
+                    seq.next_element::<i8>()?;
+                    Ok(TypeOnly)
+                }
+            }
+            deserializer.deserialize_any(Visitor)
+        }
+    }
+    assert!(matches!(from_slice::<TypeOnly>(&[0xd4, 0x0a, 0x2a]), Err(wasm_msgpack::decode::Error::TrailingElements)));
+    // nothing of the ext is left behind for the next value
+    let result = from_slice::<(serde::de::IgnoredAny, u8)>(&[0x92, 0xd4, 0x0a, 0x2a, 0x07]);
+    assert_eq!(result.unwrap().1, 7);
+}
@@ -22,6 +22,7 @@ fn test_encode_serde<T: serde::Serialize>(data: &T, expected: &[u8]) {
     print_slice(&buf[..len]);
     assert_eq!(expected.len(), len);
     assert_eq!(expected, &buf[..len]);
+    assert_eq!(expected.len(), wasm_msgpack::encode::serde::serialized_size(data).unwrap());
 }
 #[cfg(feature = "serde")]
 fn test_encode<T>(data: T, expected: &[u8])
@@ -191,11 +192,59 @@ fn encode_i64() {
     test_encode(-9223372036854775807i64, &[0xd3, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01]);
     test_encode(-9223372036854775808i64, &[0xd3, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
 }
+#[cfg(all(feature = "u64", feature = "i64"))]
+#[test]
+fn encode_int128() {
+    test_encode(5u128, &[0x05]);
+    test_encode(18446744073709551615u128, &[0xcf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+    test_encode(-3i128, &[0xfd]);
+    test_encode(300i128, &[0xcd, 0x01, 0x2c]);
+    test_encode(-9223372036854775808i128, &[0xd3, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    // too large for 64 bits
+    test_encode(
+        18446744073709551616u128,
+        &[0xc4, 0x10, 0, 0, 0, 0, 0, 0, 0, 0x01, 0, 0, 0, 0, 0, 0, 0, 0],
+    );
+    test_encode(
+        -9223372036854775809i128,
+        &[0xc4, 0x10, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+    );
+    test_encode(u128::MAX, &[&[0xc4, 0x10][..], &[0xff; 16]].concat());
+}
 #[test]
 fn encode_float() {
     test_encode(0.5f32, &[0xca, 0x3f, 0x00, 0x00, 0x00]);
     test_encode(-0.5f32, &[0xca, 0xbf, 0x00, 0x00, 0x00]);
 }
+#[cfg(feature = "serde")]
+#[test]
+fn encode_compact_floats() {
+    use wasm_msgpack::encode::serde::{to_array_with_config, SerializerOptions};
+    let options = SerializerOptions::new().compact_floats(true);
+    let mut buf = [0; 16];
+    let mut encode = |v: f64| {
+        let len = to_array_with_config(&v, &mut buf, options).unwrap();
+        buf[..len].to_vec()
+    };
+    assert_eq!(encode(5.0), [0x05]);
+    assert_eq!(encode(-3.0), [0xfd]);
+    assert_eq!(encode(300.0), [0xcd, 0x01, 0x2c]);
+    assert_eq!(encode(-2147483648.0), [0xd2, 0x80, 0x00, 0x00, 0x00]);
+    assert_eq!(encode(4294967295.0), [0xce, 0xff, 0xff, 0xff, 0xff]);
+    // too large for a 32 bit integer, but exact as f32
+    assert_eq!(encode(4294967296.0), [0xca, 0x4f, 0x80, 0x00, 0x00]);
+    assert_eq!(encode(0.5), [0xca, 0x3f, 0x00, 0x00, 0x00]);
+    assert_eq!(encode(-0.0), [0xca, 0x80, 0x00, 0x00, 0x00]);
+    assert_eq!(encode(f64::INFINITY), [0xca, 0x7f, 0x80, 0x00, 0x00]);
+    assert_eq!(encode(f64::NAN), [0xca, 0x7f, 0xc0, 0x00, 0x00]);
+    // not exact as f32
+    assert_eq!(encode(0.1), [0xcb, 0x3f, 0xb9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a]);
+
+    let len = to_array_with_config(&2.0f32, &mut buf, options).unwrap();
+    assert_eq!(&buf[..len], &[0x02]);
+    let len = to_array_with_config(&2.5f32, &mut buf, options).unwrap();
+    assert_eq!(&buf[..len], &[0xca, 0x40, 0x20, 0x00, 0x00]);
+}
 #[test]
 fn encode_map() {
     let map: &[(&str, u32)] = &[("abc", 34), ("def", 128)];
@@ -247,6 +296,213 @@ fn encode_struct() {
 }
 #[cfg(feature = "serde")]
 #[test]
+fn encode_tuple_struct() {
+    use serde::Serialize;
+    #[derive(Serialize)]
+    struct Rgb(u8, u8, u8);
+    test_encode_serde(&Rgb(1, 128, 255), &[0x93, 0x01, 0xcc, 0x80, 0xcc, 0xff]);
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_compact_struct() {
+    use serde::Serialize;
+    use wasm_msgpack::encode::serde::{serialized_size_with_config, to_array_with_config, CompactConfig};
+    #[derive(Serialize)]
+    struct Test {
+        a: Option<i32>,
+        b: u32,
+        c: &'static str,
+    }
+    let value = Test { a: None, b: 1, c: "x" };
+    let expected = &[0x93, 0xc0, 0x01, 0xa1, b'x'];
+    let mut buf = [0; 16];
+    let len = to_array_with_config(&value, &mut buf, CompactConfig).unwrap();
+    assert_eq!(&buf[..len], expected);
+    assert_eq!(serialized_size_with_config(&value, CompactConfig).unwrap(), expected.len());
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_skip_none_fields() {
+    use serde::Serialize;
+    use wasm_msgpack::encode::serde::{serialized_size_with_config, to_array_with_config, SerializerOptions, StructRepr};
+    #[derive(Serialize)]
+    struct Inner {
+        x: Option<u8>,
+    }
+    #[derive(Serialize)]
+    struct Test {
+        a: Option<i32>,
+        b: u32,
+        c: Option<Inner>,
+        d: Option<u8>,
+    }
+    let value = Test {
+        a: None,
+        b: 1,
+        c: Some(Inner { x: None }),
+        d: Some(2),
+    };
+    let options = SerializerOptions::new().skip_none_fields(true);
+    let expected = &[0x83, 0xa1, b'b', 0x01, 0xa1, b'c', 0x80, 0xa1, b'd', 0x02];
+    let mut buf = [0; 32];
+    let len = to_array_with_config(&value, &mut buf, options).unwrap();
+    assert_eq!(&buf[..len], expected);
+    assert_eq!(serialized_size_with_config(&value, options).unwrap(), expected.len());
+
+    // positional arrays can't leave out fields
+    let len = to_array_with_config(&value, &mut buf, options.struct_repr(StructRepr::Array)).unwrap();
+    assert_eq!(&buf[..len], &[0x94, 0xc0, 0x01, 0x91, 0xc0, 0x02]);
+}
+#[cfg(all(feature = "serde", feature = "map16"))]
+#[test]
+fn encode_skip_none_fields_shrinks_header() {
+    use serde::Serialize;
+    use wasm_msgpack::encode::serde::{serialized_size_with_config, to_array_with_config, SerializerOptions};
+    #[derive(Serialize, Default)]
+    struct Test {
+        a: u8,
+        b: u8,
+        c: u8,
+        d: u8,
+        e: u8,
+        f: u8,
+        g: u8,
+        h: u8,
+        i: u8,
+        j: u8,
+        k: u8,
+        l: u8,
+        m: u8,
+        n: u8,
+        o: u8,
+        p: Option<u8>,
+    }
+    let options = SerializerOptions::new().skip_none_fields(true);
+    let mut buf = [0; 64];
+    let len = to_array_with_config(&Test::default(), &mut buf, options).unwrap();
+    // 15 entries fit a fixmap, the 3 byte map16 header that was reserved for 16 entries is shrunk
+    assert_eq!(len, 1 + 15 * 3);
+    assert_eq!(buf[0], 0x8f);
+    assert_eq!(&buf[1..4], &[0xa1, b'a', 0x00]);
+    assert_eq!(&buf[len - 3..len], &[0xa1, b'o', 0x00]);
+    assert_eq!(serialized_size_with_config(&Test::default(), options).unwrap(), len);
+
+    let value = Test { p: Some(1), ..Test::default() };
+    let len = to_array_with_config(&value, &mut buf, options).unwrap();
+    assert_eq!(&buf[..3], &[0xde, 0x00, 0x10]);
+    assert_eq!(len, 3 + 16 * 3);
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_enum_by_index() {
+    use serde::Serialize;
+    use wasm_msgpack::encode::serde::{to_array_with_config, EnumRepr, SerializerOptions, StructRepr};
+    #[derive(Serialize)]
+    enum Test {
+        Unit,
+        NewType(u8),
+        Tuple(u8, u8),
+        Struct { a: u8 },
+    }
+    let options = SerializerOptions::new().enum_repr(EnumRepr::Index);
+    let mut buf = [0; 16];
+    for (value, expected) in [
+        (Test::Unit, &[0x00][..]),
+        (Test::NewType(5), &[0x81, 0x01, 0x05]),
+        (Test::Tuple(5, 6), &[0x81, 0x02, 0x92, 0x05, 0x06]),
+        (Test::Struct { a: 5 }, &[0x81, 0x03, 0x81, 0xa1, b'a', 0x05]),
+    ] {
+        let len = to_array_with_config(&value, &mut buf, options).unwrap();
+        assert_eq!(&buf[..len], expected);
+    }
+    let len = to_array_with_config(&Test::Struct { a: 5 }, &mut buf, options.struct_repr(StructRepr::Array)).unwrap();
+    assert_eq!(&buf[..len], &[0x81, 0x03, 0x91, 0x05]);
+}
+#[cfg(feature = "serde")]
+struct Entries<'a, K, V>(&'a [(K, V)]);
+#[cfg(feature = "serde")]
+impl<'a, K: serde::Serialize, V: serde::Serialize> serde::Serialize for Entries<'a, K, V> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+    }
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_canonical() {
+    use wasm_msgpack::encode::serde::{to_array_with_config, SerializerOptions};
+    let options = SerializerOptions::new().canonical(true);
+    let mut buf = [0; 64];
+
+    // sorted by the encoded keys, so shorter strings come first
+    let map = Entries(&[("aa", 1), ("b", 2), ("a", 3)]);
+    let len = to_array_with_config(&map, &mut buf, options).unwrap();
+    assert_eq!(&buf[..len], &[0x83, 0xa1, b'a', 0x03, 0xa1, b'b', 0x02, 0xa2, b'a', b'a', 0x01]);
+    assert!(wasm_msgpack::decode::is_canonical(&buf[..len]));
+
+    let map = Entries(&[(300, "x"), (-1, "y"), (5, "z")]);
+    let len = to_array_with_config(&map, &mut buf, options).unwrap();
+    assert_eq!(&buf[..len], &[0x83, 0x05, 0xa1, b'z', 0xcd, 0x01, 0x2c, 0xa1, b'x', 0xff, 0xa1, b'y']);
+
+    // NaN without payload
+    let nan = f32::from_bits(0x7fc0_0001);
+    let len = to_array_with_config(&nan, &mut buf, options).unwrap();
+    assert_eq!(&buf[..len], &[0xca, 0x7f, 0xc0, 0x00, 0x00]);
+    let nan = f64::from_bits(0xfff0_0000_0000_0001);
+    let len = to_array_with_config(&nan, &mut buf, options).unwrap();
+    assert_eq!(&buf[..len], &[0xcb, 0x7f, 0xf8, 0, 0, 0, 0, 0, 0]);
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_canonical_duplicate_key() {
+    use wasm_msgpack::encode::{
+        serde::{serialized_size_with_config, to_array_with_config, SerializerOptions},
+        Error,
+    };
+    let mut buf = [0; 64];
+    let map = Entries(&[("a", 1), ("b", 2), ("a", 3)]);
+    let result = to_array_with_config(&map, &mut buf, SerializerOptions::new().canonical(true));
+    assert!(matches!(result, Err(Error::DuplicateKey)));
+    // only checked in canonical mode
+    assert!(to_array_with_config(&map, &mut buf, SerializerOptions::new()).is_ok());
+    // and only when writing, counting doesn't keep the keys
+    assert_eq!(serialized_size_with_config(&map, SerializerOptions::new().canonical(true)).unwrap(), 10);
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_canonical_struct() {
+    use serde::Serialize;
+    use wasm_msgpack::encode::serde::{serialized_size_with_config, to_array_with_config, SerializerOptions};
+    #[derive(Serialize)]
+    struct Inner {
+        y: u8,
+        x: u8,
+    }
+    #[derive(Serialize)]
+    struct Test {
+        inner: Inner,
+        b: Option<u8>,
+        a: u8,
+    }
+    let value = Test {
+        inner: Inner { y: 1, x: 2 },
+        b: None,
+        a: 3,
+    };
+    let mut buf = [0; 64];
+    let options = SerializerOptions::new().canonical(true);
+    let expected = &[
+        0x83, 0xa1, b'a', 0x03, 0xa1, b'b', 0xc0, 0xa5, b'i', b'n', b'n', b'e', b'r', 0x82, 0xa1, b'x', 0x02, 0xa1, b'y', 0x01,
+    ];
+    let len = to_array_with_config(&value, &mut buf, options).unwrap();
+    assert_eq!(&buf[..len], expected);
+    assert_eq!(serialized_size_with_config(&value, options).unwrap(), len);
+
+    let options = options.skip_none_fields(true);
+    let len = to_array_with_config(&value, &mut buf, options).unwrap();
+    assert_eq!(&buf[..len], &[&[0x82, 0xa1, b'a', 0x03], &expected[7..]].concat()[..]);
+}
+#[cfg(feature = "serde")]
+#[test]
 fn encode_complex_struct() {
     use serde::Serialize;
     #[derive(Serialize)]
@@ -276,3 +532,390 @@ fn encode_complex_struct() {
         ],
     );
 }
+#[cfg(all(feature = "serde", any(feature = "alloc", feature = "std")))]
+#[test]
+fn encode_to_vec() {
+    use serde::Serialize;
+    #[derive(Serialize)]
+    struct Test<'a> {
+        a: &'a str,
+        b: u32,
+    }
+    let long = "x".repeat(200);
+    let data = Test { a: &long, b: 2 };
+    let mut buf = [0u8; 1000];
+    let len = wasm_msgpack::encode::serde::to_array(&data, &mut buf).unwrap();
+
+    let vec = wasm_msgpack::encode::serde::to_vec(&data).unwrap();
+    assert_eq!(&buf[..len], &vec[..]);
+
+    let mut vec = vec![0xc0];
+    let appended = wasm_msgpack::encode::serde::append_to_vec(&data, &mut vec).unwrap();
+    assert_eq!(len, appended);
+    assert_eq!(0xc0, vec[0]);
+    assert_eq!(&buf[..len], &vec[1..]);
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_to_writer() {
+    use wasm_msgpack::encode::{serde::to_writer, SizeCounter, SliceWriter, Write};
+    let data = (1u8, "abc", Binary::new(&[1, 2, 3]));
+    let expected = &[0x93, 0x01, 0xa3, 0x61, 0x62, 0x63, 0xc4, 0x03, 0x01, 0x02, 0x03];
+
+    let mut buf = [0u8; 100];
+    let mut writer = SliceWriter::new(&mut buf);
+    assert_eq!(expected.len(), to_writer(&data, &mut writer).unwrap());
+    assert_eq!(expected.len(), writer.position());
+    assert_eq!(expected, &buf[..expected.len()]);
+
+    let mut counter = SizeCounter::new();
+    assert_eq!(expected.len(), to_writer(&data, &mut counter).unwrap());
+    assert_eq!(expected.len(), counter.position());
+
+    let mut buf = [0u8; 10];
+    assert!(matches!(
+        to_writer(&data, &mut SliceWriter::new(&mut buf)),
+        Err(wasm_msgpack::encode::Error::EndOfBuffer)
+    ));
+}
+#[cfg(all(feature = "serde", feature = "std"))]
+#[test]
+fn encode_to_io_writer() {
+    use wasm_msgpack::encode::{serde::to_writer, IoWriter};
+    let data = (1u8, "abc", Binary::new(&[1, 2, 3]));
+    let mut writer = IoWriter::new(std::io::Cursor::new(Vec::new()));
+    assert_eq!(11, to_writer(&data, &mut writer).unwrap());
+    assert_eq!(
+        &[0x93, 0x01, 0xa3, 0x61, 0x62, 0x63, 0xc4, 0x03, 0x01, 0x02, 0x03],
+        &writer.into_inner().into_inner()[..]
+    );
+}
+#[cfg(all(feature = "serde", feature = "std"))]
+#[test]
+fn encode_skip_none_fields_io_writer() {
+    use serde::Serialize;
+    use wasm_msgpack::encode::{
+        serde::{to_writer_with_config, SerializerOptions},
+        Error, IoWriter,
+    };
+    #[derive(Serialize)]
+    struct Test {
+        a: Option<u8>,
+    }
+    // the map header can't be patched once it was written
+    let mut writer = IoWriter::new(Vec::new());
+    let result = to_writer_with_config(&Test { a: None }, &mut writer, SerializerOptions::new().skip_none_fields(true));
+    assert!(matches!(result, Err(Error::NotSeekable)));
+}
+#[cfg(all(feature = "serde", feature = "std"))]
+#[test]
+fn encode_canonical_io_writer() {
+    use wasm_msgpack::encode::{
+        serde::{to_writer_with_config, SerializerOptions},
+        Error, IoWriter,
+    };
+    // entries can't be reordered once they were written
+    let mut writer = IoWriter::new(Vec::new());
+    let result = to_writer_with_config(&Entries(&[(1, 2)]), &mut writer, SerializerOptions::new().canonical(true));
+    assert!(matches!(result, Err(Error::NotSeekable)));
+    assert!(writer.into_inner().is_empty());
+}
+#[test]
+fn encode_length_exceeds_format() {
+    use wasm_msgpack::encode::{
+        serialize_array_start, serialize_bin_start, serialize_map_start, serialize_str_start, Error, MAX_ARRAY_LEN, MAX_BIN_LEN,
+        MAX_MAP_LEN, MAX_STR_LEN,
+    };
+    let mut buf = [0u8; 100];
+    assert!(matches!(serialize_str_start(MAX_STR_LEN + 1, &mut buf), Err(Error::LengthExceedsFormat { max }) if max == MAX_STR_LEN));
+    assert!(matches!(serialize_bin_start(MAX_BIN_LEN + 1, &mut buf), Err(Error::LengthExceedsFormat { max }) if max == MAX_BIN_LEN));
+    assert!(matches!(serialize_array_start(MAX_ARRAY_LEN + 1, &mut buf), Err(Error::LengthExceedsFormat { max }) if max == MAX_ARRAY_LEN));
+    assert!(matches!(serialize_map_start(MAX_MAP_LEN + 1, &mut buf), Err(Error::LengthExceedsFormat { max }) if max == MAX_MAP_LEN));
+
+    // the value itself is too long
+    let data = [0u8; 256];
+    #[cfg(not(feature = "bin16"))]
+    assert!(matches!(Binary::new(&data).write_into_slice(&mut [0u8; 300]), Err(Error::LengthExceedsFormat { max: 255 })));
+    let text = core::str::from_utf8(&data).unwrap();
+    #[cfg(not(feature = "str16"))]
+    assert!(matches!(text.write_into_slice(&mut [0u8; 300]), Err(Error::LengthExceedsFormat { max: 255 })));
+    #[cfg(feature = "str16")]
+    assert_eq!(text.write_into_slice(&mut [0u8; 300]).unwrap(), 259);
+}
+#[test]
+fn encode_header_end_of_buffer() {
+    use wasm_msgpack::encode::{serialize_array_start, serialize_map_start, Error};
+    // the 16 and 32 bit headers don't fit
+    #[cfg(feature = "array16")]
+    assert!(matches!(serialize_array_start(0x10, &mut [0u8; 2]), Err(Error::EndOfBuffer)));
+    #[cfg(feature = "array32")]
+    assert!(matches!(serialize_array_start(0x10000, &mut [0u8; 4]), Err(Error::EndOfBuffer)));
+    #[cfg(feature = "map16")]
+    assert!(matches!(serialize_map_start(0x10, &mut [0u8; 2]), Err(Error::EndOfBuffer)));
+    #[cfg(feature = "map32")]
+    assert!(matches!(serialize_map_start(0x10000, &mut [0u8; 4]), Err(Error::EndOfBuffer)));
+    assert!(matches!(serialize_array_start(0, &mut []), Err(Error::EndOfBuffer)));
+    assert!(matches!(serialize_map_start(0, &mut []), Err(Error::EndOfBuffer)));
+}
+#[cfg(all(feature = "serde", not(feature = "str16")))]
+#[test]
+fn encode_serde_length_exceeds_format() {
+    let text = core::str::from_utf8(&[b'a'; 256]).unwrap();
+    let mut buf = [0u8; 300];
+    let result = wasm_msgpack::encode::serde::to_array(text, &mut buf);
+    assert!(matches!(result, Err(wasm_msgpack::encode::Error::LengthExceedsFormat { max: 255 })));
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_collect_str() {
+    use wasm_msgpack::encode::{serde::to_array, Error};
+    // `fmt::Arguments` is serialized with `collect_str`
+    test_encode_serde(&format_args!("{}-{}", 1, "ab"), &[0xa4, b'1', b'-', b'a', b'b']);
+    let mut expected = vec![0xd9, 40, b'x'];
+    expected.extend_from_slice(&[b' '; 39]);
+    test_encode_serde(&format_args!("{:40}", "x"), &expected);
+    test_encode_serde(&(format_args!("{}", 7u8), 8u8), &[0x92, 0xa1, b'7', 0x08]);
+
+    let mut buf = [0u8; 10];
+    assert!(matches!(to_array(&format_args!("{:40}", "x"), &mut buf), Err(Error::EndOfBuffer)));
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_chunked() {
+    use serde::Serialize;
+    use wasm_msgpack::encode::serde::{to_array, to_chunks};
+    #[derive(Serialize)]
+    struct Test<'a> {
+        name: &'a str,
+        values: [u32; 4],
+        flag: bool,
+    }
+    let value = Test {
+        name: "a string that is longer than the chunks",
+        values: [1, 300, 70000, 5],
+        flag: true,
+    };
+    let mut expected = [0u8; 100];
+    let len = to_array(&value, &mut expected).unwrap();
+    for chunk_len in 1..=len + 1 {
+        let mut chunk = vec![0u8; chunk_len];
+        let mut chunks = Vec::new();
+        let written = to_chunks(&value, &mut chunk, |c| {
+            chunks.push(c.to_vec());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(written, len);
+        // all chunks but the last one are full
+        let (last, full) = chunks.split_last().unwrap();
+        assert!(full.iter().all(|c| c.len() == chunk_len));
+        assert!(!last.is_empty() && last.len() <= chunk_len);
+        assert_eq!(&expected[..len], &chunks.concat()[..]);
+    }
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_chunked_errors() {
+    use wasm_msgpack::encode::{
+        serde::{to_chunks, to_chunks_with_config, SerializerOptions},
+        Error,
+    };
+    assert!(matches!(to_chunks(&[1u8, 2, 3], &mut [], |_| Ok(())), Err(Error::EndOfBuffer)));
+    let mut chunk = [0u8; 2];
+    let mut chunks = Vec::new();
+    let written = to_chunks(&[1u8, 2, 3], &mut chunk, |c| {
+        chunks.push(c.to_vec());
+        Ok(())
+    });
+    assert_eq!(written.unwrap(), 4);
+    assert_eq!(chunks, [[0x93, 0x01], [0x02, 0x03]]);
+
+    // the error of the callback stops the serializer
+    let mut calls = 0;
+    let result = to_chunks(&[1u8, 2, 3, 4, 5], &mut chunk, |_| {
+        calls += 1;
+        Err(Error::CustomError)
+    });
+    assert!(matches!(result, Err(Error::CustomError)));
+    assert_eq!(calls, 1);
+
+    // the header of a map can't be patched once it was handed out
+    let result = to_chunks_with_config(&Entries(&[(1, 2)]), &mut chunk, |_| Ok(()), SerializerOptions::new().canonical(true));
+    assert!(matches!(result, Err(Error::NotSeekable)));
+    assert!(matches!(to_chunks(&Unsized(&[1u8]), &mut chunk, |_| Ok(())), Err(Error::NotSeekable)));
+}
+#[cfg(feature = "serde")]
+struct Failing(&'static str);
+#[cfg(feature = "serde")]
+impl serde::Serialize for Failing {
+    fn serialize<S: serde::Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+        Err(serde::ser::Error::custom(self.0))
+    }
+}
+#[cfg(all(feature = "serde", feature = "custom-error-messages"))]
+#[test]
+fn encode_custom_error() {
+    let mut buf = [0u8; 10];
+    let err = wasm_msgpack::encode::serde::to_array(&[Failing("invalid value")], &mut buf).unwrap_err();
+    assert_eq!(err.to_string(), "invalid value");
+
+    // too long for the message buffer without std
+    let long = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef_overflow";
+    let err = wasm_msgpack::encode::serde::to_array(&Failing(long), &mut buf).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(err.to_string(), long);
+    #[cfg(not(feature = "std"))]
+    assert_eq!(err.to_string(), &long[..64]);
+}
+#[cfg(all(feature = "serde", not(feature = "custom-error-messages")))]
+#[test]
+fn encode_custom_error() {
+    let mut buf = [0u8; 10];
+    let err = wasm_msgpack::encode::serde::to_array(&Failing("invalid value"), &mut buf).unwrap_err();
+    assert!(matches!(err, wasm_msgpack::encode::Error::CustomError));
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_serialized_size() {
+    fn assert_size<T: serde::Serialize + ?Sized>(data: &T) {
+        let mut buf = [0u8; 100000];
+        let len = wasm_msgpack::encode::serde::to_array(data, &mut buf).unwrap();
+        assert_eq!(len, wasm_msgpack::encode::serde::serialized_size(data).unwrap());
+    }
+    assert_size(&0u8);
+    assert_size(&-33i16);
+    assert_size(&65536u32);
+    assert_size(&1.5f64);
+    assert_size("");
+    assert_size(&"a".repeat(31));
+    assert_size(&"a".repeat(32));
+    assert_size(&"a".repeat(255));
+    #[cfg(feature = "str16")]
+    assert_size(&"a".repeat(256));
+    #[cfg(feature = "str32")]
+    assert_size(&"a".repeat(70000));
+    assert_size(&Binary::new(&[1u8; 255]));
+    #[cfg(feature = "bin16")]
+    assert_size(&Binary::new(&[1u8; 256]));
+    #[cfg(feature = "bin32")]
+    assert_size(&Binary::new(&[1u8; 70000]));
+    assert_size(&[Some(1u8), None]);
+    assert_size(&[("a", 1u8), ("b", 2u8)]);
+}
+#[cfg(all(feature = "serde", feature = "ext"))]
+#[test]
+fn encode_serialized_size_ext() {
+    use wasm_msgpack::{encode::serde::serialized_size, Ext};
+    for len in [1usize, 2, 3, 4, 8, 16, 17, 255] {
+        let data = [7u8; 255];
+        let ext = Ext::new(3, &data[..len]);
+        let mut buf = [0u8; 300];
+        let expected = wasm_msgpack::encode::serde::to_array(&ext, &mut buf).unwrap();
+        assert_eq!(expected, serialized_size(&ext).unwrap());
+    }
+}
+#[cfg(all(feature = "serde", feature = "timestamp"))]
+#[test]
+fn encode_serialized_size_timestamp() {
+    use wasm_msgpack::{encode::serde::serialized_size, timestamp::Timestamp};
+    // timestamp 32
+    assert_eq!(6, serialized_size(&Timestamp::new(1514862245, 0).unwrap()).unwrap());
+    // timestamp 64
+    assert_eq!(10, serialized_size(&Timestamp::new(1514862245, 678901234).unwrap()).unwrap());
+    // timestamp 96
+    #[cfg(feature = "timestamp96")]
+    assert_eq!(15, serialized_size(&Timestamp::new(-1, 0).unwrap()).unwrap());
+}
+/// Serializes its items through `serialize_seq(None)`
+#[cfg(feature = "serde")]
+struct Unsized<'a, T>(&'a [T]);
+#[cfg(feature = "serde")]
+impl<'a, T: serde::Serialize> serde::Serialize for Unsized<'a, T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().filter(|_| true))
+    }
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_unsized_seq() {
+    test_encode_serde(&Unsized::<u8>(&[]), &[0x90]);
+    test_encode_serde(&Unsized(&[1u8, 2, 3]), &[0x93, 0x01, 0x02, 0x03]);
+    test_encode_serde(&Unsized(&[Unsized(&[1u8]), Unsized(&[])]), &[0x92, 0x91, 0x01, 0x90]);
+    #[cfg(feature = "array16")]
+    {
+        let mut expected = vec![0xdc, 0x00, 0x14];
+        expected.extend_from_slice(&[7u8; 20]);
+        test_encode_serde(&Unsized(&[7u8; 20]), &expected);
+    }
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_unsized_map() {
+    use serde::Serialize;
+    #[derive(Serialize)]
+    struct Inner {
+        b: u8,
+        c: u8,
+    }
+    #[derive(Serialize)]
+    struct Outer {
+        a: u8,
+        #[serde(flatten)]
+        inner: Inner,
+    }
+    test_encode_serde(
+        &Outer { a: 1, inner: Inner { b: 2, c: 3 } },
+        &[0x83, 0xa1, 0x61, 0x01, 0xa1, 0x62, 0x02, 0xa1, 0x63, 0x03],
+    );
+}
+#[cfg(all(feature = "serde", any(feature = "alloc", feature = "std")))]
+#[test]
+fn encode_unsized_to_vec() {
+    let mut vec = vec![0xc0];
+    wasm_msgpack::encode::serde::append_to_vec(&Unsized(&[1u8, 2, 3]), &mut vec).unwrap();
+    assert_eq!(&[0xc0, 0x93, 0x01, 0x02, 0x03], &vec[..]);
+}
+#[cfg(all(feature = "serde", feature = "std"))]
+#[test]
+fn encode_unsized_io_writer() {
+    use wasm_msgpack::encode::{serde::to_writer, Error, IoWriter};
+    let mut writer = IoWriter::new(Vec::new());
+    assert!(matches!(to_writer(&Unsized(&[1u8]), &mut writer), Err(Error::NotSeekable)));
+    assert!(writer.into_inner().is_empty());
+}
+#[cfg(feature = "serde")]
+#[test]
+fn encode_exact_size_buffer() {
+    use serde::Serialize;
+    use wasm_msgpack::encode::{
+        serde::{serialized_size_with_config, to_array_with_config, DefaultConfig, SerializerConfig, SerializerOptions},
+        Error,
+    };
+    fn assert_exact<T: Serialize + ?Sized, C: SerializerConfig>(data: &T, config: C) {
+        let size = serialized_size_with_config(data, config).unwrap();
+        let mut buf = vec![0u8; size];
+        assert_eq!(to_array_with_config(data, &mut buf, config).unwrap(), size);
+        let mut expected = vec![0u8; size + 16];
+        assert_eq!(to_array_with_config(data, &mut expected, config).unwrap(), size);
+        assert_eq!(buf, expected[..size]);
+        assert!(matches!(to_array_with_config(data, &mut buf[..size - 1], config), Err(Error::EndOfBuffer)));
+    }
+    // headers of unknown length are reserved no wider than the rest of the buffer
+    assert_exact(&Unsized(&[1u8]), DefaultConfig);
+    assert_exact(&Unsized(&[Unsized(&[1u8]), Unsized(&[])]), DefaultConfig);
+    assert_exact(&Unsized(&[7u8; 15]), DefaultConfig);
+    #[cfg(feature = "array16")]
+    assert_exact(&Unsized(&[7u8; 16]), DefaultConfig);
+    assert_exact(&format_args!("{}-{}", 1, "ab"), DefaultConfig);
+
+    // `None` fields are left out without writing them first
+    #[derive(Serialize)]
+    struct Test {
+        a: u8,
+        b: Option<u8>,
+    }
+    let options = SerializerOptions::new().skip_none_fields(true);
+    assert_exact(&Test { a: 1, b: None }, options);
+    assert_exact(&Test { a: 1, b: Some(2) }, options);
+    assert_exact(&[Test { a: 1, b: None }, Test { a: 2, b: None }], options);
+}
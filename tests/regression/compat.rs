@@ -14,6 +14,9 @@ pub enum TestEnum {
     StructVariant { a: i32, b: u8 },
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestTupleStruct(u8, String, i32);
+
 #[rstest::rstest]
 #[case(TestEnum::Empty)]
 #[case(&1_u32)]
@@ -23,6 +26,15 @@ pub enum TestEnum {
 #[case(&TestEnum::StructType {
     inner: "HelloWorld".to_owned()
 })]
+#[case(&TestTupleStruct(1, "HelloWorld".to_owned(), -300))]
+#[case(&TestEnum::UnitVariant)]
+#[case(&TestEnum::NewTypeVariant(-300))]
+#[case(&TestEnum::TupleVariant(1, 2))]
+#[case(&TestEnum::StructVariant { a: -1, b: 200 })]
+#[case(&u128::MAX)]
+#[case(&18446744073709551616u128)]
+#[case(&i128::MIN)]
+#[case(&-9223372036854775809i128)]
 
 fn test<T>(#[case] item: T)
 where
@@ -32,6 +44,38 @@ where
     assert_same(&item);
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestStruct {
+    id: u32,
+    name: String,
+    inner: Option<TestTupleStruct>,
+}
+
+#[test]
+fn compact_struct() {
+    let item = TestStruct {
+        id: 70000,
+        name: "HelloWorld".to_owned(),
+        inner: Some(TestTupleStruct(1, "a".to_owned(), -1)),
+    };
+    let mut buf = [0; 1024];
+    let written = wasm_msgpack::encode::serde::to_array_with_config(&item, &mut buf, wasm_msgpack::encode::serde::CompactConfig).unwrap();
+    assert_eq!(&buf[..written], &rmp_serialize_compact(&item)[..]);
+    let decoded: TestStruct = decode::from_slice(&buf[..written]).unwrap();
+    assert_eq!(decoded, item);
+}
+
+#[rstest::rstest]
+#[case(TestEnum::StructType { inner: "HelloWorld".to_owned() })]
+#[case(TestEnum::StructVariant { a: -1, b: 200 })]
+fn compact_struct_variant(#[case] item: TestEnum) {
+    let mut buf = [0; 1024];
+    let written = wasm_msgpack::encode::serde::to_array_with_config(&item, &mut buf, wasm_msgpack::encode::serde::CompactConfig).unwrap();
+    assert_eq!(&buf[..written], &rmp_serialize_compact(&item)[..]);
+    let decoded: TestEnum = decode::from_slice(&buf[..written]).unwrap();
+    assert_eq!(decoded, item);
+}
+
 #[test]
 fn json_null() {
     let bytes = r#"{"nullval":null}"#;
@@ -68,7 +112,19 @@ where
     assert_eq!(our_str, rmp_str);
 }
 
+/// Serialized by rmp-serde with structs as maps, like the default config
 fn rmp_serialize<T>(item: &T) -> Vec<u8>
+where
+    T: ?Sized + Serialize,
+{
+    let mut buff = Vec::new();
+    let mut serializer = rmp_serde::encode::Serializer::new(&mut buff).with_binary().with_struct_map();
+    item.serialize(&mut serializer).unwrap();
+    buff
+}
+
+/// Serialized by rmp-serde with structs as tuples, like `CompactConfig`
+fn rmp_serialize_compact<T>(item: &T) -> Vec<u8>
 where
     T: ?Sized + Serialize,
 {
@@ -6,6 +6,8 @@ pub enum TestEnum {
     Empty,
     NewType(String),
     StructType { inner: String },
+    TupleType(i32, String),
+    MultiStructType { a: i32, b: String },
 }
 
 #[test]
@@ -26,6 +28,35 @@ fn enum_structtype() {
     assert_rt(&expected);
 }
 
+#[test]
+fn enum_tupletype() {
+    let expected = TestEnum::TupleType(-1, "HelloWorld".to_owned());
+    assert_rt(&expected);
+}
+#[test]
+fn enum_multistructtype() {
+    let expected = TestEnum::MultiStructType {
+        a: 300,
+        b: "HelloWorld".to_owned(),
+    };
+    assert_rt(&expected);
+}
+#[test]
+fn enum_in_struct() {
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Wrapper {
+        first: TestEnum,
+        second: TestEnum,
+        after: u8,
+    }
+    let expected = Wrapper {
+        first: TestEnum::TupleType(1, "a".to_owned()),
+        second: TestEnum::MultiStructType { a: 2, b: "b".to_owned() },
+        after: 3,
+    };
+    assert_rt(&expected);
+}
+
 fn assert_rt<T>(expected: &T)
 where
     T: ?Sized + Serialize + DeserializeOwned + std::fmt::Debug + PartialEq + 'static,
@@ -49,3 +80,101 @@ where
     let written = wasm_msgpack::encode::serde::to_array(item, &mut buf).unwrap();
     buf[0..written].to_vec()
 }
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Inner {
+    id: u32,
+    name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum InternallyTagged {
+    Empty,
+    NewType(Inner),
+    StructType { a: i32, b: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "t", content = "c")]
+pub enum AdjacentlyTagged {
+    Empty,
+    NewType(String),
+    TupleType(i32, String),
+    StructType { a: i32, b: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Untagged {
+    Empty,
+    Int(i64),
+    Text(String),
+    TupleType(i32, String),
+    StructType { a: i32, b: Option<String> },
+}
+
+#[test]
+fn enum_internally_tagged() {
+    assert_rt(&InternallyTagged::Empty);
+    assert_rt(&InternallyTagged::NewType(Inner {
+        id: 7,
+        name: "seven".to_owned(),
+    }));
+    assert_rt(&InternallyTagged::StructType {
+        a: -300,
+        b: "HelloWorld".to_owned(),
+    });
+    assert_rt(&vec![
+        InternallyTagged::Empty,
+        InternallyTagged::StructType { a: 1, b: "b".to_owned() },
+    ]);
+}
+
+#[test]
+fn enum_adjacently_tagged() {
+    assert_rt(&AdjacentlyTagged::Empty);
+    assert_rt(&AdjacentlyTagged::NewType("HelloWorld".to_owned()));
+    assert_rt(&AdjacentlyTagged::TupleType(-1, "HelloWorld".to_owned()));
+    assert_rt(&AdjacentlyTagged::StructType {
+        a: 300,
+        b: "HelloWorld".to_owned(),
+    });
+}
+
+#[test]
+fn enum_untagged() {
+    assert_rt(&Untagged::Empty);
+    assert_rt(&Untagged::Int(-70000));
+    assert_rt(&Untagged::Int(3));
+    assert_rt(&Untagged::Text("HelloWorld".to_owned()));
+    assert_rt(&Untagged::TupleType(-1, "HelloWorld".to_owned()));
+    assert_rt(&Untagged::StructType { a: 2, b: None });
+}
+
+#[test]
+fn enum_tagged_with_config() {
+    use wasm_msgpack::encode::serde::{to_array_with_config, CompactConfig, SerializerConfig, SerializerOptions};
+    fn assert_rt_with<T, C>(expected: &T, config: C)
+    where
+        T: Serialize + DeserializeOwned + std::fmt::Debug + PartialEq,
+        C: SerializerConfig,
+    {
+        let mut buf = [0; 1024];
+        let len = to_array_with_config(expected, &mut buf, config).unwrap();
+        let actual: T = wasm_msgpack::decode::from_slice(&buf[..len]).unwrap();
+        assert_eq!(expected, &actual);
+    }
+
+    // structs as arrays, the tag is the first element then
+    assert_rt_with(&InternallyTagged::Empty, CompactConfig);
+    assert_rt_with(&InternallyTagged::StructType { a: 1, b: "b".to_owned() }, CompactConfig);
+    assert_rt_with(&AdjacentlyTagged::TupleType(1, "b".to_owned()), CompactConfig);
+    // serde only matches untagged struct variants against maps, not against arrays
+    assert_rt_with(&Untagged::TupleType(1, "b".to_owned()), CompactConfig);
+
+    // leaving out `None` fields
+    let options = SerializerOptions::new().skip_none_fields(true);
+    assert_rt_with(&Untagged::StructType { a: 1, b: None }, options);
+    assert_rt_with(&InternallyTagged::NewType(Inner { id: 1, name: "a".to_owned() }), options);
+}
@@ -126,6 +126,21 @@ fn roundtrip_i64() {
     test_roundtrip(-9223372036854775808i64);
 }
 #[test]
+fn roundtrip_int128() {
+    test_roundtrip(0u128);
+    test_roundtrip(4294967295u128);
+    test_roundtrip(18446744073709551616u128);
+    test_roundtrip(u128::MAX);
+    test_roundtrip(-1i128);
+    test_roundtrip(-2147483648i128);
+    test_roundtrip(i128::MIN);
+    test_roundtrip(i128::MAX);
+    #[cfg(all(feature = "u64", feature = "i64"))]
+    test_roundtrip(-9223372036854775809i128);
+    #[cfg(all(feature = "u64", feature = "i64"))]
+    test_roundtrip(18446744073709551615i128);
+}
+#[test]
 fn roundtrip_float() {
     test_roundtrip(0.5f32);
     test_roundtrip(-0.5f32);
@@ -193,3 +208,210 @@ fn roundtrip_complex_struct() {
         c: (2, [3, 4, 5]),
     });
 }
+#[test]
+fn roundtrip_flatten() {
+    use serde::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Inner {
+        b: u32,
+        c: Option<i8>,
+    }
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Outer {
+        a: u32,
+        #[serde(flatten)]
+        inner: Inner,
+    }
+    test_roundtrip(Outer {
+        a: 1,
+        inner: Inner { b: 2, c: Some(3) },
+    });
+}
+#[test]
+fn roundtrip_tuple_struct() {
+    use serde::{Deserialize, Serialize};
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Rgb(u8, u8, u8);
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Pixel {
+        pos: (u16, u16),
+        color: Rgb,
+    }
+    test_roundtrip(Rgb(1, 128, 255));
+    test_roundtrip(Pixel {
+        pos: (3, 300),
+        color: Rgb(0, 0, 0),
+    });
+}
+#[test]
+fn roundtrip_compact_struct() {
+    use serde::{Deserialize, Serialize};
+    use wasm_msgpack::encode::serde::{to_array_with_config, CompactConfig};
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Inner {
+        x: i16,
+        y: Option<u8>,
+    }
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Outer {
+        name: String,
+        inner: Inner,
+        list: Vec<Inner>,
+    }
+    let data = Outer {
+        name: "outer".to_owned(),
+        inner: Inner { x: 300, y: None },
+        list: vec![Inner { x: 1, y: Some(2) }, Inner { x: 3, y: None }],
+    };
+    let mut buf = [0u8; 1000];
+    let len = to_array_with_config(&data, &mut buf, CompactConfig).unwrap();
+    print_slice(&buf[..len]);
+    assert_eq!(buf[0], 0x93);
+    let v: Outer = wasm_msgpack::decode::from_slice(&buf[..len]).unwrap();
+    assert_eq!(data, v);
+}
+#[cfg(feature = "timestamp")]
+#[test]
+fn roundtrip_compact_timestamp() {
+    use wasm_msgpack::encode::serde::{to_array_with_config, CompactConfig};
+    use wasm_msgpack::timestamp::Timestamp;
+    let ts = Timestamp::new(1514862245, 678901234).unwrap();
+    let mut buf = [0u8; 32];
+    let len = to_array_with_config(&ts, &mut buf, CompactConfig).unwrap();
+    let v: Timestamp = wasm_msgpack::decode::from_slice(&buf[..len]).unwrap();
+    assert_eq!(ts, v);
+}
+#[test]
+fn roundtrip_skip_none_fields() {
+    use serde::{Deserialize, Serialize};
+    use wasm_msgpack::encode::serde::{to_array_with_config, SerializerOptions};
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Test {
+        a: Option<i32>,
+        b: String,
+        c: Option<Vec<Option<u8>>>,
+    }
+    let options = SerializerOptions::new().skip_none_fields(true);
+    for data in [
+        Test {
+            a: None,
+            b: "b".to_owned(),
+            c: None,
+        },
+        Test {
+            a: Some(-5),
+            b: "b".to_owned(),
+            c: Some(vec![None, Some(1)]),
+        },
+    ] {
+        let mut buf = [0u8; 100];
+        let len = to_array_with_config(&data, &mut buf, options).unwrap();
+        print_slice(&buf[..len]);
+        let v: Test = wasm_msgpack::decode::from_slice(&buf[..len]).unwrap();
+        assert_eq!(data, v);
+    }
+}
+#[test]
+fn roundtrip_struct_variant() {
+    use serde::{Deserialize, Serialize};
+    use wasm_msgpack::decode::{from_slice_with_config, DeserializerOptions};
+    use wasm_msgpack::encode::serde::{to_array_with_config, SerializerOptions, StructRepr};
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Test {
+        Struct { a: u16, b: Option<String> },
+    }
+    for repr in [StructRepr::Map, StructRepr::Array] {
+        for skip_none in [false, true] {
+            let options = SerializerOptions::new().struct_repr(repr).skip_none_fields(skip_none);
+            // the fields of struct variants are encoded like those of structs
+            let only = DeserializerOptions::new().struct_repr(Some(repr));
+            for data in [
+                Test::Struct { a: 1, b: None },
+                Test::Struct {
+                    a: 300,
+                    b: Some("b".to_owned()),
+                },
+            ] {
+                let mut buf = [0u8; 100];
+                let len = to_array_with_config(&data, &mut buf, options).unwrap();
+                print_slice(&buf[..len]);
+                let v: Test = from_slice_with_config(&buf[..len], only).unwrap();
+                assert_eq!(data, v);
+                let v: Test = wasm_msgpack::decode::from_slice(&buf[..len]).unwrap();
+                assert_eq!(data, v);
+            }
+        }
+    }
+    // `None` fields are left out of the map
+    let mut buf = [0u8; 100];
+    let len = to_array_with_config(&Test::Struct { a: 1, b: None }, &mut buf, SerializerOptions::new().skip_none_fields(true)).unwrap();
+    assert_eq!(&buf[..len], &[0x81, 0xa6, b'S', b't', b'r', b'u', b'c', b't', 0x81, 0xa1, b'a', 0x01]);
+}
+#[test]
+fn roundtrip_enum_by_index() {
+    use serde::{Deserialize, Serialize};
+    use wasm_msgpack::encode::serde::{to_array_with_config, EnumRepr, SerializerOptions};
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Test {
+        Unit,
+        NewType(String),
+        Tuple(i8, Option<u8>),
+        Struct { a: u16, b: Vec<Test> },
+    }
+    let options = SerializerOptions::new().enum_repr(EnumRepr::Index);
+    for data in [
+        Test::Unit,
+        Test::NewType("new".to_owned()),
+        Test::Tuple(-1, None),
+        Test::Struct {
+            a: 1000,
+            b: vec![Test::Unit, Test::Tuple(1, Some(2))],
+        },
+    ] {
+        let mut buf = [0u8; 100];
+        let len = to_array_with_config(&data, &mut buf, options).unwrap();
+        print_slice(&buf[..len]);
+        let v: Test = wasm_msgpack::decode::from_slice(&buf[..len]).unwrap();
+        assert_eq!(data, v);
+    }
+}
+#[test]
+fn roundtrip_canonical() {
+    use std::collections::HashMap;
+    use wasm_msgpack::encode::serde::{to_array_with_config, SerializerOptions};
+    let options = SerializerOptions::new().canonical(true);
+    // the same maps, built in a different order
+    let inner = |i: u32| (0..i % 7).map(move |j| (j * 1000, i % 2 == 0));
+    let first: HashMap<String, HashMap<u32, bool>> = (0..15).map(|i| (format!("key{}", i), inner(i).collect())).collect();
+    let second: HashMap<String, HashMap<u32, bool>> = (0..15).rev().map(|i| (format!("key{}", i), inner(i).rev().collect())).collect();
+    let mut buf = [0u8; 1000];
+    let len = to_array_with_config(&first, &mut buf, options).unwrap();
+    assert!(wasm_msgpack::decode::is_canonical(&buf[..len]));
+    let mut buf2 = [0u8; 1000];
+    let len2 = to_array_with_config(&second, &mut buf2, options).unwrap();
+    assert_eq!(&buf[..len], &buf2[..len2]);
+    let v: HashMap<String, HashMap<u32, bool>> = wasm_msgpack::decode::from_slice(&buf[..len]).unwrap();
+    assert_eq!(first, v);
+}
+#[test]
+fn roundtrip_compact_floats() {
+    use serde::{Deserialize, Serialize};
+    use wasm_msgpack::encode::serde::{to_array_with_config, SerializerOptions};
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Test {
+        a: f32,
+        b: f64,
+        c: Vec<f64>,
+    }
+    let data = Test {
+        a: 3.0,
+        b: -1.5,
+        c: vec![0.0, -0.0, 1e10, 0.1, -70000.0, f64::MAX, f64::INFINITY],
+    };
+    let mut buf = [0u8; 100];
+    let len = to_array_with_config(&data, &mut buf, SerializerOptions::new().compact_floats(true)).unwrap();
+    print_slice(&buf[..len]);
+    let v: Test = wasm_msgpack::decode::from_slice(&buf[..len]).unwrap();
+    assert_eq!(data, v);
+    assert!(v.c[1].is_sign_negative());
+}